@@ -0,0 +1,116 @@
+use sha2::{Digest, Sha256};
+
+use std::{
+    cmp::Reverse,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Where the package database synced from `server_url` gets cached
+pub fn db_file(cache_dir: &Path, server_url: &str) -> PathBuf {
+    cache_dir.join(format!("{}.db", key(server_url)))
+}
+
+/// Where the `ETag` for the cached database at `db_file` gets stored
+pub fn etag_file(db_file: &Path) -> PathBuf {
+    db_file.with_extension("etag")
+}
+
+// A short hash keeps odd characters in the url out of the file name
+fn key(server_url: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(server_url.trim_end_matches('/').as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    hash[..16].to_owned()
+}
+
+/// Removes the least recently synced databases until the cache fits within `max_size` bytes. The
+/// database at `keep` is never removed. Returns the removed files
+pub fn evict(cache_dir: &Path, keep: &Path, max_size: u64) -> io::Result<Vec<PathBuf>> {
+    let mut cached = Vec::new();
+    for dir_entry in fs::read_dir(cache_dir)? {
+        let path = dir_entry?.path();
+        if path.extension().is_some_and(|extension| extension == "db") {
+            let metadata = fs::metadata(&path)?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            cached.push((modified, metadata.len(), path));
+        }
+    }
+    // Newest first so that the oldest are left over once the limit is hit
+    cached.sort_by_key(|(modified, _, _)| Reverse(*modified));
+
+    let mut total = cached
+        .iter()
+        .filter(|(_, _, path)| path == keep)
+        .map(|(_, size, _)| size)
+        .sum::<u64>();
+    let mut evicted = Vec::new();
+    for (_, size, path) in cached.into_iter().filter(|(_, _, path)| path != keep) {
+        if total + size > max_size {
+            fs::remove_file(&path)?;
+            // A leftover tag would keep the next sync from downloading the database again
+            match fs::remove_file(etag_file(&path)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            evicted.push(path);
+        } else {
+            total += size;
+        }
+    }
+
+    Ok(evicted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn per_server_entries() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache_dir = temp_dir.path();
+
+        let first = db_file(cache_dir, "http://first.example.com");
+        let second = db_file(cache_dir, "http://second.example.com");
+        assert_ne!(first, second);
+        assert_eq!(first, db_file(cache_dir, "http://first.example.com/"));
+
+        fs::write(&first, [0; 10])?;
+        fs::write(&second, [0; 10])?;
+        assert_eq!(fs::read_dir(cache_dir)?.count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn evict_oldest() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache_dir = temp_dir.path();
+        let files: Vec<_> = ["old", "middle", "new"]
+            .iter()
+            .map(|server| db_file(cache_dir, server))
+            .collect();
+        for file in &files {
+            fs::write(file, [0; 10])?;
+            fs::write(etag_file(file), "\"tag\"")?;
+            // Keep the modified times distinct
+            thread::sleep(Duration::from_millis(20));
+        }
+        fs::write(cache_dir.join("not-a-db.txt"), [0; 100])?;
+
+        // The active server is kept even though it's the oldest
+        let evicted = evict(cache_dir, &files[0], 20)?;
+        assert_eq!(evicted, [files[1].clone()]);
+        assert!(!etag_file(&files[1]).exists());
+        assert!(files[0].exists());
+        assert!(etag_file(&files[0]).exists());
+        assert!(files[2].exists());
+        assert!(cache_dir.join("not-a-db.txt").exists());
+
+        Ok(())
+    }
+}
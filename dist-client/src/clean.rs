@@ -0,0 +1,78 @@
+use anyhow::Result;
+use dist_package::manifest::Version;
+use dist_package_db::database::DistpacDB;
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::download::Torrents;
+
+/// Finds the torrent data in `data_dir` that doesn't belong to any installed package
+///
+/// Only entries named like `<name>-<version>` are considered since anything else wasn't put there
+/// by a package, and data that still has a torrent is left alone so nothing gets pulled out from
+/// under transmission
+pub fn orphaned(
+    data_dir: &Path,
+    installed_db: &DistpacDB,
+    torrents: &impl Torrents,
+) -> Result<Vec<PathBuf>> {
+    if !data_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let installed: HashSet<_> = installed_db
+        .list_all()?
+        .into_iter()
+        .map(|entry| entry.torrent_name().to_owned())
+        .collect();
+
+    let mut orphans = Vec::new();
+    for dir_entry in fs::read_dir(data_dir)? {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name();
+        let name = file_name.to_string_lossy();
+        if is_package_data(&name)
+            && !installed.contains(name.as_ref())
+            && torrents.get_by_name(&name).is_none()
+        {
+            orphans.push(dir_entry.path());
+        }
+    }
+    orphans.sort();
+
+    Ok(orphans)
+}
+
+// Torrent names are always `<name>-<version>`
+fn is_package_data(name: &str) -> bool {
+    match name.rsplit_once('-') {
+        Some((name, version)) => !name.is_empty() && version.parse::<Version>().is_ok(),
+        None => false,
+    }
+}
+
+/// Total size of the file or everything within the directory
+pub fn disk_usage(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        let mut total = 0;
+        for dir_entry in fs::read_dir(path)? {
+            total += disk_usage(&dir_entry?.path())?;
+        }
+        Ok(total)
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+pub fn remove(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
@@ -1,4 +1,9 @@
-use clap::Clap;
+use clap::{ArgEnum, Clap};
+use dist_package_db::database::SortBy;
+
+use crate::config::ServerUrl;
+
+use std::{path::PathBuf, time::Duration};
 
 /// Basic program for managing the distpac client. This includes operations for syncing the package
 /// listing from the server, listing, and searching from the package listing, and finally installing
@@ -11,20 +16,81 @@ pub struct Opts {
     /// Increase verbosity
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: usize,
+    /// Directory to download packages into instead of the default data directory.
+    #[clap(long, global = true)]
+    pub download_dir: Option<PathBuf>,
+    /// Skip confirming before installing, removing, or cleaning.
+    #[clap(short, long, global = true)]
+    pub yes: bool,
+    /// Print what installing, upgrading, removing, or cleaning would do without changing anything.
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+    /// Disable colored output. This is also done when `NO_COLOR` is set or output isn't a terminal.
+    #[clap(long, global = true)]
+    pub no_color: bool,
+    /// Use this server instead of the ones listed in the config.
+    #[clap(long, global = true)]
+    pub server: Option<ServerUrl>,
+    /// Directory for the synced package databases instead of the default cache directory.
+    #[clap(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+    /// How download progress gets shown. `json` writes a JSON event per line to stdout instead of
+    /// drawing progress bars.
+    #[clap(long, global = true, arg_enum, default_value = "bar")]
+    pub progress: ProgressFormat,
+    /// Milliseconds to wait between checking on downloads, from 50 to 5000.
+    #[clap(long, global = true, default_value = "200", parse(try_from_str = parse_interval))]
+    pub interval: Duration,
     #[clap(subcommand)]
     pub subcmd: SubCommand,
 }
 
+const MIN_INTERVAL_MS: u64 = 50;
+const MAX_INTERVAL_MS: u64 = 5_000;
+
+fn parse_interval(millis: &str) -> Result<Duration, String> {
+    let millis: u64 = millis.parse().map_err(|err| format!("{}", err))?;
+    if (MIN_INTERVAL_MS..=MAX_INTERVAL_MS).contains(&millis) {
+        Ok(Duration::from_millis(millis))
+    } else {
+        Err(format!(
+            "Expected {} to {} milliseconds, but got {}",
+            MIN_INTERVAL_MS, MAX_INTERVAL_MS, millis
+        ))
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ProgressFormat {
+    Bar,
+    Json,
+}
+
 #[derive(Clap, Debug)]
 pub enum SubCommand {
+    /// Write a template config to fill in.
+    InitConfig,
     /// Sync the package listing with the server.
-    Sync,
-    /// Install the listed package.
-    Install(Package),
+    Sync(SyncOpts),
+    /// Install the listed packages.
+    Install(Packages),
+    /// Upgrade the listed packages, or every installed package if none are listed.
+    Upgrade(Packages),
     /// Remove the installed package.
     Remove(Package),
+    /// Show the installed packages that have a newer version available.
+    Outdated,
+    /// Remove downloaded data for packages that are no longer installed.
+    Clean,
     /// Operations related to listing packages.
     List(ListOpts),
+    /// Search the package listing.
+    Search(SearchQuery),
+    /// Show details about a listed package.
+    Info(Package),
+    /// Check the downloaded data of the listed packages, or every installed package if none are
+    /// listed.
+    Verify(VerifyOpts),
 }
 
 #[derive(Clap, Debug)]
@@ -33,9 +99,81 @@ pub struct Package {
     pub name: String,
 }
 
+#[derive(Clap, Debug)]
+pub struct Packages {
+    /// Packages to operate on. A version can be picked with `name@1.2.3` or `name>=1.2.0` when
+    /// installing.
+    pub names: Vec<String>,
+    /// Maximum number of packages to download at once.
+    #[clap(short, long, default_value = "4")]
+    pub jobs: usize,
+}
+
+#[derive(Clap, Debug)]
+pub struct SyncOpts {
+    /// Seconds to wait on an unresponsive server before giving up instead of the config's
+    /// `sync_timeout_secs`.
+    #[clap(long)]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Clap, Debug)]
+pub struct VerifyOpts {
+    /// Installed packages to verify.
+    pub names: Vec<String>,
+    /// Have the torrent client re-check corrupt packages and download any bad pieces again.
+    #[clap(long)]
+    pub repair: bool,
+}
+
 #[derive(Clap, Debug)]
 pub struct ListOpts {
     /// List only installed packages instead of all available.
     #[clap(long)]
     pub installed: bool,
+    /// How the packages get displayed.
+    #[clap(long, arg_enum, default_value = "human")]
+    pub format: Format,
+    /// Show at most this many packages.
+    #[clap(long)]
+    pub limit: Option<usize>,
+    /// Skip this many packages before showing any.
+    #[clap(long, default_value = "0")]
+    pub offset: usize,
+    /// What the packages get ordered by.
+    #[clap(long, arg_enum, default_value = "name")]
+    pub sort: Sort,
+    /// Flip the order of the packages.
+    #[clap(long)]
+    pub reverse: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Sort {
+    Name,
+    Version,
+    Size,
+}
+
+impl From<Sort> for SortBy {
+    fn from(sort: Sort) -> Self {
+        match sort {
+            Sort::Name => Self::Name,
+            Sort::Version => Self::Version,
+            Sort::Size => Self::Size,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Clap, Debug)]
+pub struct SearchQuery {
+    /// Terms that must all be in a package's name or description to match.
+    #[clap(required = true)]
+    pub query_terms: Vec<String>,
 }
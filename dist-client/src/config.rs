@@ -1,18 +1,336 @@
 use anyhow::Result;
 use serde::Deserialize;
+use url::Url;
 
-use std::fs::File;
+use std::{
+    convert::TryFrom,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    slice,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{
+    download::Seeding,
+    sync::{self, AgentOpts},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
-    pub server_url: String,
+    /// Either a single server or a list of mirrors to try in order
+    #[serde(alias = "server_url")]
+    pub server_urls: ServerUrls,
+    /// How many attempts are made against each mirror when syncing hits a transient error
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Base64 encoded ed25519 key that synced package databases must be signed with. Signatures
+    /// are only checked when this is set
+    pub public_key: Option<String>,
+    /// Where to get the signature from instead of `packages.db.sig` on the synced mirror
+    pub signature_url: Option<String>,
+    /// Size in bytes that the cached package databases are kept under
+    #[serde(default = "default_max_cache_size")]
+    pub max_cache_size: u64,
+    /// Whether packages keep seeding after they're downloaded, which helps out the swarm
+    #[serde(default = "default_seed_after_install")]
+    pub seed_after_install: bool,
+    /// The ratio that downloaded packages stop seeding at
+    pub seed_ratio_limit: Option<f32>,
+    /// A PEM encoded CA certificate to trust when syncing from mirrors that use a private CA
+    pub ca_cert_path: Option<PathBuf>,
+    /// The proxy to sync through, which falls back to `HTTPS_PROXY` or `HTTP_PROXY` when unset
+    pub proxy: Option<String>,
+    /// Seconds to wait on an unresponsive server while syncing
+    #[serde(default = "default_sync_timeout_secs")]
+    pub sync_timeout_secs: u64,
+    /// Size in bytes that upgrades have to go over before asking for confirmation
+    #[serde(default = "default_confirm_threshold")]
+    pub confirm_threshold: u64,
+}
+
+/// The bare minimum that a config needs, which gets written out by `init-config`
+pub const TEMPLATE: &str = "\
+# The url of the server that hosts the package database
+server_url: http://package.server
+";
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "No config file found at {}. Create one with the url of the package server, e.g.\n\n{}\nor \
+    run `dist-client init-config` to write a template",
+    .0.display(),
+    TEMPLATE
+)]
+pub struct MissingConfig(pub PathBuf);
+
+fn default_max_cache_size() -> u64 {
+    50_000_000
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_confirm_threshold() -> u64 {
+    1_000_000_000
+}
+
+fn default_sync_timeout_secs() -> u64 {
+    sync::DEFAULT_TIMEOUT.as_secs()
+}
+
+fn default_seed_after_install() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(try_from = "RawServerUrls")]
+pub enum ServerUrls {
+    Single(ServerUrl),
+    Mirrors(Vec<ServerUrl>),
+}
+
+// Validating after picking the variant keeps the error from getting swallowed by the untagged enum
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawServerUrls {
+    Single(String),
+    Mirrors(Vec<String>),
+}
+
+impl TryFrom<RawServerUrls> for ServerUrls {
+    type Error = InvalidServerUrl;
+
+    fn try_from(raw: RawServerUrls) -> Result<Self, Self::Error> {
+        match raw {
+            RawServerUrls::Single(url) => Ok(Self::Single(url.parse()?)),
+            RawServerUrls::Mirrors(urls) => Ok(Self::Mirrors(
+                urls.iter()
+                    .map(|url| url.parse())
+                    .collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+}
+
+impl ServerUrls {
+    pub fn as_slice(&self) -> &[ServerUrl] {
+        match self {
+            Self::Single(url) => slice::from_ref(url),
+            Self::Mirrors(urls) => urls,
+        }
+    }
+}
+
+/// The url of a package server, which has to be http or https and name a host
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerUrl(Url);
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum InvalidServerUrl {
+    #[error("Server url {0} isn't a valid url Error: {1}")]
+    Malformed(String, url::ParseError),
+    #[error("Server url {0} has to start with http:// or https://")]
+    UnsupportedScheme(String),
+    #[error("Server url {0} is missing a host")]
+    MissingHost(String),
+}
+
+impl ServerUrl {
+    /// The url without a trailing `/`, ready to have paths tacked on
+    pub fn base(&self) -> &str {
+        self.0.as_str().trim_end_matches('/')
+    }
+}
+
+impl FromStr for ServerUrl {
+    type Err = InvalidServerUrl;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(s).map_err(|err| InvalidServerUrl::Malformed(s.to_owned(), err))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(InvalidServerUrl::UnsupportedScheme(s.to_owned()));
+        }
+        if url.host_str().is_none_or(str::is_empty) {
+            return Err(InvalidServerUrl::MissingHost(s.to_owned()));
+        }
+
+        Ok(Self(url))
+    }
 }
 
 impl Config {
+    pub fn seeding(&self) -> Seeding {
+        Seeding {
+            keep_seeding: self.seed_after_install,
+            ratio_limit: self.seed_ratio_limit,
+        }
+    }
+
+    pub fn agent_opts(&self) -> AgentOpts {
+        AgentOpts {
+            ca_cert: self.ca_cert_path.clone(),
+            proxy: self.proxy.clone().or_else(sync::env_proxy),
+            timeout: Duration::from_secs(self.sync_timeout_secs),
+        }
+    }
+
     pub fn try_new() -> Result<Self> {
-        let config_path = dist_utils::path::client_config_file();
-        let config_file = File::open(&config_path)?;
+        Self::from_file(&dist_utils::path::client_config_file())
+    }
+
+    pub fn from_file(config_path: &Path) -> Result<Self> {
+        let config_file = match File::open(config_path) {
+            Ok(config_file) => config_file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(MissingConfig(config_path.to_owned()).into())
+            }
+            Err(err) => return Err(err.into()),
+        };
         let config: Config = serde_yaml::from_reader(config_file)?;
         Ok(config)
     }
 }
+
+/// Writes out the [`TEMPLATE`] as long as there's no config already
+pub fn write_template(config_path: &Path) -> Result<()> {
+    if let Some(config_dir) = config_path.parent() {
+        fs::create_dir_all(config_dir)?;
+    }
+    let mut config_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(config_path)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => {
+                anyhow::anyhow!("A config already exists at {}", config_path.display())
+            }
+            _ => err.into(),
+        })?;
+    config_file.write_all(TEMPLATE.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_server() -> Result<()> {
+        let config: Config = serde_yaml::from_str("server_url: http://localhost:8000")?;
+        assert_eq!(
+            config.server_urls.as_slice(),
+            ["http://localhost:8000".parse()?]
+        );
+        assert_eq!(
+            config.server_urls.as_slice()[0].base(),
+            "http://localhost:8000"
+        );
+        assert_eq!(config.retries, 3);
+        assert_eq!(config.public_key, None);
+        assert_eq!(config.seeding(), Seeding::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mirrors() -> Result<()> {
+        let config: Config = serde_yaml::from_str(
+            "server_urls:\n  - http://first.example.com\n  - http://second.example.com\nretries: 5",
+        )?;
+        assert_eq!(
+            config.server_urls,
+            ServerUrls::Mirrors(vec![
+                "http://first.example.com".parse()?,
+                "http://second.example.com".parse()?
+            ])
+        );
+        assert_eq!(config.server_urls.as_slice().len(), 2);
+        assert_eq!(config.retries, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn server_urls() -> Result<()> {
+        let url: ServerUrl = "https://mirror.example.com:8443/distpac/".parse()?;
+        assert_eq!(url.base(), "https://mirror.example.com:8443/distpac");
+
+        assert!(matches!(
+            "htp://package.server".parse::<ServerUrl>(),
+            Err(InvalidServerUrl::UnsupportedScheme(_))
+        ));
+        assert!(matches!(
+            "package.server:8000".parse::<ServerUrl>(),
+            Err(InvalidServerUrl::UnsupportedScheme(_))
+        ));
+        assert!(matches!(
+            "package.server".parse::<ServerUrl>(),
+            Err(InvalidServerUrl::Malformed(..))
+        ));
+        assert!(matches!(
+            "http://".parse::<ServerUrl>(),
+            Err(InvalidServerUrl::Malformed(..))
+        ));
+        assert!(matches!(
+            "file:///srv/packages".parse::<ServerUrl>(),
+            Err(InvalidServerUrl::UnsupportedScheme(_))
+        ));
+
+        // The reason makes it through to the config error, even for a single bad mirror
+        let err = serde_yaml::from_str::<Config>(
+            "server_urls:\n  - http://first.example.com\n  - htp://second.example.com",
+        )
+        .err()
+        .ok_or_else(|| anyhow::anyhow!("The typo should be caught"))?;
+        assert!(err
+            .to_string()
+            .contains("Server url htp://second.example.com has to start with http:// or https://"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_config() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let config_path = temp_dir.path().join("client.yaml");
+
+        let err = Config::from_file(&config_path)
+            .err()
+            .ok_or_else(|| anyhow::anyhow!("The config shouldn't exist"))?;
+        assert!(err.downcast_ref::<MissingConfig>().is_some());
+        let message = err.to_string();
+        assert!(message.contains(&config_path.display().to_string()));
+        assert!(message.contains("server_url: http://package.server"));
+
+        // The template works as is and doesn't clobber an existing config
+        write_template(&config_path)?;
+        let config = Config::from_file(&config_path)?;
+        assert_eq!(
+            config.server_urls.as_slice()[0].base(),
+            "http://package.server"
+        );
+        assert!(write_template(&config_path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn seeding() -> Result<()> {
+        let config: Config = serde_yaml::from_str(
+            "server_url: http://localhost:8000\nseed_after_install: false\nseed_ratio_limit: 1.5",
+        )?;
+        assert_eq!(
+            config.seeding(),
+            Seeding {
+                keep_seeding: false,
+                ratio_limit: Some(1.5)
+            }
+        );
+
+        Ok(())
+    }
+}
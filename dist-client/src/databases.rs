@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use dist_package_db::{
+    database::{DistpacDB, MissingDBAction},
+    error::DatabaseError,
+};
+
+use std::path::Path;
+
+use crate::error::ClientError;
+
+/// The package database only ever comes from syncing, so a missing one is an error
+pub fn package_db_action() -> MissingDBAction {
+    MissingDBAction::RaiseError
+}
+
+/// The installed database starts out empty, and a corrupt one is better off starting over than
+/// blocking every command
+pub fn installed_db_action() -> MissingDBAction {
+    MissingDBAction::Recreate
+}
+
+pub fn open_package_db(package_db_file: &Path) -> Result<DistpacDB> {
+    DistpacDB::connect(package_db_file, package_db_action()).map_err(|err| match err {
+        // There's nothing worth keeping in a corrupt copy, so point towards getting a fresh one
+        DatabaseError::Corrupt(_) => anyhow::Error::new(err).context(ClientError::DbCorrupt),
+        DatabaseError::MissingDatabase => anyhow::Error::new(err)
+            .context("The package database hasn't been synced yet. Run `dist-client sync` first"),
+        err => anyhow::Error::new(err).context("Failed opening the package database"),
+    })
+}
+
+pub fn open_installed_db() -> Result<DistpacDB> {
+    DistpacDB::connect(
+        &dist_utils::path::installed_db_file(),
+        installed_db_action(),
+    )
+    .context("Failed opening the installed database")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn db_actions() -> BoxResult<()> {
+        assert!(matches!(package_db_action(), MissingDBAction::RaiseError));
+        assert!(matches!(installed_db_action(), MissingDBAction::Recreate));
+
+        let temp_dir = tempfile::tempdir()?;
+        let err = open_package_db(&temp_dir.path().join("packages.db"))
+            .err()
+            .ok_or("Opened a missing package database")?;
+        assert!(err.to_string().contains("dist-client sync"));
+
+        Ok(())
+    }
+}
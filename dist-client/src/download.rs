@@ -0,0 +1,566 @@
+use anyhow::Result;
+use dist_package_db::models::PackageEntry;
+use indicatif::ProgressBar;
+use log::warn;
+use serde_json::json;
+use transmission_wrapper::{entry::Entry, error::Error, Transmission};
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use crate::{error::ClientError, resolve::Failures};
+
+/// The parts of a torrent client needed to download packages
+pub trait Torrents {
+    fn download_torrent(&mut self, magnet: &str) -> Result<(), Error>;
+    fn refresh(&mut self) -> Result<(), Error>;
+    fn get_by_name(&self, name: &str) -> Option<&Entry>;
+    fn remove_torrent(&mut self, id: u64) -> Result<(), Error>;
+    fn stop_torrent(&mut self, id: u64) -> Result<(), Error>;
+    fn set_seed_ratio(&mut self, id: u64, ratio: f32) -> Result<(), Error>;
+}
+
+impl Torrents for Transmission {
+    fn download_torrent(&mut self, magnet: &str) -> Result<(), Error> {
+        Transmission::download_torrent(self, magnet)
+    }
+
+    fn refresh(&mut self) -> Result<(), Error> {
+        Transmission::refresh(self).map(|_| ())
+    }
+
+    fn get_by_name(&self, name: &str) -> Option<&Entry> {
+        Transmission::get_by_name(self, name)
+    }
+
+    fn remove_torrent(&mut self, id: u64) -> Result<(), Error> {
+        Transmission::remove_torrent(self, id)
+    }
+
+    fn stop_torrent(&mut self, id: u64) -> Result<(), Error> {
+        Transmission::pause_torrent(self, id)
+    }
+
+    fn set_seed_ratio(&mut self, id: u64, ratio: f32) -> Result<(), Error> {
+        Transmission::set_seed_ratio(self, id, ratio)
+    }
+}
+
+/// What happens to a torrent once its package finishes downloading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Seeding {
+    pub keep_seeding: bool,
+    /// Only used while seeding
+    pub ratio_limit: Option<f32>,
+}
+
+impl Default for Seeding {
+    fn default() -> Self {
+        Self {
+            keep_seeding: true,
+            ratio_limit: None,
+        }
+    }
+}
+
+impl Seeding {
+    fn apply(&self, torrents: &mut impl Torrents, id: u64) -> Result<(), Error> {
+        if !self.keep_seeding {
+            return torrents.stop_torrent(id);
+        }
+
+        match self.ratio_limit {
+            Some(ratio) => torrents.set_seed_ratio(id, ratio),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Somewhere to report how each download is going
+pub trait Progress {
+    fn update(&mut self, entry: &PackageEntry, torrent: &Entry);
+    fn finish(&mut self, entry: &PackageEntry);
+    fn abandon(&mut self, entry: &PackageEntry);
+}
+
+/// The usual progress bars, keyed by each package's torrent name
+impl Progress for HashMap<String, ProgressBar> {
+    // Going off the fraction keeps the bar right even if the torrent's size doesn't quite match the
+    // listed size
+    fn update(&mut self, entry: &PackageEntry, torrent: &Entry) {
+        if let Some(bar) = self.get(entry.torrent_name()) {
+            let position = bar.length() as f64 * f64::from(torrent.progress_fraction());
+            bar.set_position(position as u64);
+        }
+    }
+
+    fn finish(&mut self, entry: &PackageEntry) {
+        if let Some(bar) = self.get(entry.torrent_name()) {
+            bar.finish();
+        }
+    }
+
+    fn abandon(&mut self, entry: &PackageEntry) {
+        if let Some(bar) = self.get(entry.torrent_name()) {
+            bar.abandon();
+        }
+    }
+}
+
+/// Writes each update as a line of JSON for front-ends to consume instead of drawing bars
+pub struct JsonEvents<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonEvents<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    // Failing to report progress isn't worth failing the download over
+    fn emit(&mut self, event: serde_json::Value) {
+        if let Err(err) = writeln!(self.writer, "{}", event).and_then(|_| self.writer.flush()) {
+            warn!("Failed writing progress event Error: {}", err);
+        }
+    }
+}
+
+impl<W: Write> Progress for JsonEvents<W> {
+    fn update(&mut self, entry: &PackageEntry, torrent: &Entry) {
+        self.emit(json!({
+            "event": "progress",
+            "name": entry.name(),
+            "downloaded": u64::from(*torrent.downloaded()),
+            "total": entry.size(),
+        }));
+    }
+
+    fn finish(&mut self, entry: &PackageEntry) {
+        self.emit(json!({ "event": "finished", "name": entry.name() }));
+    }
+
+    fn abandon(&mut self, entry: &PackageEntry) {
+        self.emit(json!({ "event": "failed", "name": entry.name() }));
+    }
+}
+
+// A package that has been handed off to the torrent client
+struct Active {
+    index: usize,
+    entry: PackageEntry,
+    // The torrent takes a moment to show up after getting added, so it's only an error for it to
+    // go missing after it has been seen
+    seen: bool,
+}
+
+/// Downloads the packages with up to `max_active` torrents going at once, reporting the progress
+/// of each one on every refresh
+///
+/// The downloaded packages are returned in the same order they were passed in, so dependencies
+/// still come before the packages that need them
+///
+/// Each finished torrent is left seeding or gets stopped depending on `seeding`
+///
+/// Once `interrupted` gets set, the unfinished torrents are removed and this errors with
+/// [`ClientError::Interrupted`] instead. Their partial data is left for `clean` to handle
+pub fn download_all(
+    torrents: &mut impl Torrents,
+    entries: Vec<PackageEntry>,
+    progress: &mut impl Progress,
+    max_active: usize,
+    interval: Duration,
+    seeding: &Seeding,
+    interrupted: &AtomicBool,
+) -> Result<(Vec<PackageEntry>, Failures)> {
+    let max_active = max_active.max(1);
+    let mut queued = entries.into_iter().enumerate();
+    let mut active: Vec<Active> = Vec::new();
+    let mut finished = Vec::new();
+    let mut failures = Vec::new();
+
+    // Torrents left over from an earlier run get picked back up instead of being added again
+    torrents.refresh()?;
+
+    loop {
+        // Keep the number of active downloads topped off
+        while active.len() < max_active {
+            let (index, entry) = match queued.next() {
+                Some(next) => next,
+                None => break,
+            };
+
+            if torrents.get_by_name(entry.torrent_name()).is_some() {
+                active.push(Active {
+                    index,
+                    entry,
+                    seen: true,
+                });
+                continue;
+            }
+
+            match torrents.download_torrent(entry.magnet()) {
+                Ok(()) => active.push(Active {
+                    index,
+                    entry,
+                    seen: false,
+                }),
+                Err(err) => {
+                    progress.abandon(&entry);
+                    // `Ctrl-C` also kills the running `transmission-remote`
+                    if interrupted.load(Ordering::SeqCst) {
+                        return Err(abandon_active(torrents, active, progress));
+                    }
+                    failures.push((
+                        entry.name().to_owned(),
+                        anyhow::Error::new(err).context(ClientError::DownloadFailed),
+                    ));
+                }
+            }
+        }
+
+        if active.is_empty() {
+            break;
+        }
+
+        if let Err(err) = torrents.refresh() {
+            if interrupted.load(Ordering::SeqCst) {
+                return Err(abandon_active(torrents, active, progress));
+            }
+            return Err(err.into());
+        }
+        let mut still_active = Vec::new();
+        for mut download in active.drain(..) {
+            match torrents.get_by_name(download.entry.torrent_name()) {
+                Some(torrent) => {
+                    download.seen = true;
+                    progress.update(&download.entry, torrent);
+
+                    if torrent.is_finished() {
+                        // The package is already downloaded, so this isn't worth failing over
+                        let id = *torrent.id();
+                        progress.finish(&download.entry);
+                        if let Err(err) = seeding.apply(torrents, id) {
+                            warn!(
+                                "Failed updating the seeding for {} Error: {}",
+                                download.entry.name(),
+                                err
+                            );
+                        }
+                        finished.push((download.index, download.entry));
+                    } else {
+                        still_active.push(download);
+                    }
+                }
+                None if download.seen => {
+                    progress.abandon(&download.entry);
+                    failures.push((
+                        download.entry.name().to_owned(),
+                        anyhow::anyhow!("The torrent was removed before it finished downloading")
+                            .context(ClientError::DownloadFailed),
+                    ));
+                }
+                None => still_active.push(download),
+            }
+        }
+        active = still_active;
+
+        if interrupted.load(Ordering::SeqCst) {
+            return Err(abandon_active(torrents, active, progress));
+        }
+
+        if !active.is_empty() {
+            thread::sleep(interval);
+        }
+    }
+
+    finished.sort_by_key(|(index, _)| *index);
+    let finished = finished.into_iter().map(|(_, entry)| entry).collect();
+    Ok((finished, failures))
+}
+
+// Removes the torrents that were still downloading when the download got interrupted
+fn abandon_active(
+    torrents: &mut impl Torrents,
+    active: Vec<Active>,
+    progress: &mut impl Progress,
+) -> anyhow::Error {
+    for download in active {
+        progress.abandon(&download.entry);
+        let id = torrents
+            .get_by_name(download.entry.torrent_name())
+            .map(|torrent| *torrent.id());
+        if let Some(id) = id {
+            if let Err(err) = torrents.remove_torrent(id) {
+                warn!(
+                    "Failed removing the torrent for {} Error: {}",
+                    download.entry.name(),
+                    err
+                );
+            }
+        }
+    }
+
+    ClientError::Interrupted.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dist_package::manifest::Version;
+
+    use std::sync::Arc;
+
+    use crate::test_utils::FakeTorrents;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    fn package(name: &str) -> PackageEntry {
+        PackageEntry::new(
+            name.to_owned(),
+            Version::new(1, 0, 0),
+            format!("magnet:?xt=urn:btih:{}", name),
+            100,
+        )
+    }
+
+    fn names(packages: &[PackageEntry]) -> Vec<&str> {
+        packages
+            .iter()
+            .map(|package| package.name().as_str())
+            .collect()
+    }
+
+    fn hidden_bars(packages: &[&PackageEntry]) -> HashMap<String, ProgressBar> {
+        packages
+            .iter()
+            .map(|package| (package.torrent_name().to_owned(), ProgressBar::hidden()))
+            .collect()
+    }
+
+    #[test]
+    fn simultaneous_downloads() -> BoxResult<()> {
+        let (slow, fast) = (package("slow"), package("fast"));
+        let mut torrents = FakeTorrents::new(&[(&slow, 3), (&fast, 1)]);
+        let mut bars = hidden_bars(&[&slow, &fast]);
+
+        let (finished, failures) = download_all(
+            &mut torrents,
+            vec![slow, fast],
+            &mut bars,
+            4,
+            Duration::from_millis(1),
+            &Seeding::default(),
+            &AtomicBool::new(false),
+        )?;
+        assert!(failures.is_empty());
+        // Both are going at the same time with the fast one finishing first, but the original order
+        // is kept
+        assert_eq!(
+            torrents.events,
+            [
+                "added slow-1.0.0",
+                "added fast-1.0.0",
+                "finished fast-1.0.0",
+                "finished slow-1.0.0"
+            ]
+        );
+        assert_eq!(names(&finished), ["slow", "fast"]);
+        assert!(bars.values().all(ProgressBar::is_finished));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bounded_downloads() -> BoxResult<()> {
+        let (first, second, missing) = (package("first"), package("second"), package("missing"));
+        let mut torrents = FakeTorrents::new(&[(&first, 2), (&second, 1)]);
+        let mut bars = hidden_bars(&[&first, &second, &missing]);
+
+        let (finished, failures) = download_all(
+            &mut torrents,
+            vec![first, missing, second],
+            &mut bars,
+            1,
+            Duration::from_millis(1),
+            &Seeding::default(),
+            &AtomicBool::new(false),
+        )?;
+        assert_eq!(
+            torrents.events,
+            [
+                "added first-1.0.0",
+                "finished first-1.0.0",
+                "added second-1.0.0",
+                "finished second-1.0.0"
+            ]
+        );
+        assert_eq!(names(&finished), ["first", "second"]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "missing");
+
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_downloads() -> BoxResult<()> {
+        // The first refresh is for picking up existing torrents. A refresh that fails from the
+        // interrupt doesn't get to update anything, so it has to come a refresh later
+        for &(refreshes, kills_remote) in &[(2, false), (3, true)] {
+            let (slow, fast) = (package("slow"), package("fast"));
+            let interrupted = Arc::new(AtomicBool::new(false));
+            let mut torrents = FakeTorrents::new(&[(&slow, 4), (&fast, 1)]);
+            torrents.interrupt = Some((refreshes, Arc::clone(&interrupted)));
+            torrents.kills_remote = kills_remote;
+            let mut bars = hidden_bars(&[&slow, &fast]);
+
+            let err = download_all(
+                &mut torrents,
+                vec![slow, fast],
+                &mut bars,
+                4,
+                Duration::from_millis(1),
+                &Seeding::default(),
+                &interrupted,
+            )
+            .err()
+            .ok_or("The download wasn't interrupted")?;
+            assert_eq!(crate::error::find(&err), Some(&ClientError::Interrupted));
+            // Only the unfinished torrent gets removed
+            assert_eq!(
+                torrents.events,
+                [
+                    "added slow-1.0.0",
+                    "added fast-1.0.0",
+                    "finished fast-1.0.0",
+                    "removed slow-1.0.0"
+                ]
+            );
+            assert!(torrents.get_by_name("slow-1.0.0").is_none());
+            assert!(bars.values().all(ProgressBar::is_finished));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn seeding_policy() -> BoxResult<()> {
+        let download = |seeding| -> BoxResult<Vec<String>> {
+            let package = package("seeded");
+            let mut torrents = FakeTorrents::new(&[(&package, 1)]);
+            download_all(
+                &mut torrents,
+                vec![package],
+                &mut JsonEvents::new(Vec::new()),
+                1,
+                Duration::from_millis(1),
+                &seeding,
+                &AtomicBool::new(false),
+            )?;
+            Ok(torrents.events)
+        };
+
+        // Seeding is left alone by default
+        assert_eq!(
+            download(Seeding::default())?,
+            ["added seeded-1.0.0", "finished seeded-1.0.0"]
+        );
+        assert_eq!(
+            download(Seeding {
+                keep_seeding: true,
+                ratio_limit: Some(1.5)
+            })?,
+            [
+                "added seeded-1.0.0",
+                "finished seeded-1.0.0",
+                "seed ratio 1.5 seeded-1.0.0"
+            ]
+        );
+        // The ratio doesn't matter when the torrent gets stopped anyways
+        assert_eq!(
+            download(Seeding {
+                keep_seeding: false,
+                ratio_limit: Some(1.5)
+            })?,
+            [
+                "added seeded-1.0.0",
+                "finished seeded-1.0.0",
+                "stopped seeded-1.0.0"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_events() -> BoxResult<()> {
+        let (slow, fast) = (package("slow"), package("fast"));
+        let mut torrents = FakeTorrents::new(&[(&slow, 2), (&fast, 1)]);
+        let mut events = JsonEvents::new(Vec::new());
+
+        let (finished, failures) = download_all(
+            &mut torrents,
+            vec![slow, fast],
+            &mut events,
+            4,
+            Duration::from_millis(1),
+            &Seeding::default(),
+            &AtomicBool::new(false),
+        )?;
+        assert!(failures.is_empty());
+        assert_eq!(finished.len(), 2);
+
+        let events: Vec<serde_json::Value> = String::from_utf8(events.writer)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            events,
+            [
+                json!({"event": "progress", "name": "slow", "downloaded": 50, "total": 100}),
+                json!({"event": "progress", "name": "fast", "downloaded": 100, "total": 100}),
+                json!({"event": "finished", "name": "fast"}),
+                json!({"event": "progress", "name": "slow", "downloaded": 100, "total": 100}),
+                json!({"event": "finished", "name": "slow"}),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resume_existing_downloads() -> BoxResult<()> {
+        let (existing, fresh) = (package("existing"), package("fresh"));
+        let mut torrents =
+            FakeTorrents::new(&[(&existing, 1), (&fresh, 1)]).with_existing(&existing, 3);
+        let mut bars = hidden_bars(&[&existing, &fresh]);
+
+        let (finished, failures) = download_all(
+            &mut torrents,
+            vec![existing, fresh],
+            &mut bars,
+            4,
+            Duration::from_millis(1),
+            &Seeding::default(),
+            &AtomicBool::new(false),
+        )?;
+        assert!(failures.is_empty());
+        assert_eq!(names(&finished), ["existing", "fresh"]);
+        // Only the new torrent gets added
+        assert_eq!(
+            torrents.events,
+            [
+                "added fresh-1.0.0",
+                "finished fresh-1.0.0",
+                "finished existing-1.0.0"
+            ]
+        );
+
+        Ok(())
+    }
+}
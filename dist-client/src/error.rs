@@ -0,0 +1,68 @@
+use dist_package::spec::VersionReq;
+
+/// The failures that scripts might want to tell apart, each with its own exit code
+///
+/// These get attached to an `anyhow::Error` anywhere in its chain, usually as context
+#[derive(thiserror::Error, Clone, Debug, PartialEq)]
+pub enum ClientError {
+    #[error("No package entry found for: {0}")]
+    PackageNotFound(String),
+    #[error("No version of {0} satisfies {1}")]
+    NoMatchingVersion(String, VersionReq),
+    #[error("Failed syncing the package database")]
+    SyncFailed,
+    #[error("Timed out waiting on {0}")]
+    TimedOut(String),
+    #[error("Failed downloading the package")]
+    DownloadFailed,
+    #[error("The package database is corrupt. Run `dist-client sync` to download a fresh copy")]
+    DbCorrupt,
+    #[error("Interrupted")]
+    Interrupted,
+}
+
+impl ClientError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::PackageNotFound(_) | Self::NoMatchingVersion(..) => 3,
+            Self::SyncFailed | Self::TimedOut(_) => 4,
+            Self::DownloadFailed => 5,
+            Self::DbCorrupt => 6,
+            // The usual code for getting stopped by `SIGINT`
+            Self::Interrupted => 130,
+        }
+    }
+}
+
+/// The outermost `ClientError` in the chain
+pub fn find(err: &anyhow::Error) -> Option<&ClientError> {
+    // Downcasting the `anyhow::Error` itself is what sees through the layers of context
+    err.downcast_ref::<ClientError>().or_else(|| {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<ClientError>())
+    })
+}
+
+/// Anything that isn't a `ClientError` gets the usual exit code of 1
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    find(err).map(ClientError::exit_code).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::Context;
+
+    #[test]
+    fn nested_exit_code() {
+        let err = anyhow::anyhow!("Connection refused")
+            .context(ClientError::SyncFailed)
+            .context("Failed running sync");
+        assert_eq!(find(&err), Some(&ClientError::SyncFailed));
+        assert_eq!(exit_code(&err), 4);
+
+        let err: anyhow::Result<()> = Err(anyhow::anyhow!("Something else")).context("Oops");
+        assert_eq!(exit_code(&err.unwrap_err()), 1);
+    }
+}
@@ -1,35 +1,83 @@
 use anyhow::{Context, Result};
 use clap::Clap;
 use colored::Colorize;
-use dist_package_db::{
-    database::{DistpacDB, MissingDBAction},
-    models::PackageEntry,
-};
-use indicatif::{ProgressBar, ProgressStyle};
-use log::debug;
+use dist_package::checksum;
+use dist_package_db::{database::DistpacDB, models::PackageEntry};
+use dist_utils::misc::TORRENT_LABEL;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::{debug, warn};
+use signal_hook::consts::SIGINT;
 use transmission_wrapper::{bytes::Bytes, Transmission, TransmissionOpts};
 
 use std::{
-    fs::File,
-    io::{self, BufWriter, Write},
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsStr,
+    fs,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    process,
+    sync::{atomic::AtomicBool, Arc},
     thread,
     time::Duration,
 };
 
 use crate::{
-    cli::{ListOpts, Opts, Package, SubCommand},
+    cli::{
+        Format, ListOpts, Opts, Package, Packages, ProgressFormat, SearchQuery, SubCommand,
+        SyncOpts, VerifyOpts,
+    },
     config::Config,
+    download::{JsonEvents, Seeding, Torrents},
+    error::ClientError,
+    resolve::{Failures, Outdated},
+    verify::Integrity,
 };
 
+mod cache;
+mod clean;
 mod cli;
 mod config;
+mod databases;
+mod download;
+mod error;
+mod prompt;
+mod resolve;
+mod scripts;
+mod sync;
+#[cfg(test)]
+mod test_utils;
+mod verify;
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {:?}", err);
+        process::exit(error::exit_code(&err));
+    }
+}
+
+fn run() -> Result<()> {
     let Opts {
         quiet,
         verbose,
+        download_dir,
+        yes,
+        dry_run,
+        no_color,
+        server,
+        cache_dir,
+        progress,
+        interval,
         subcmd,
     } = Opts::parse();
+    let no_color_env = env::var_os("NO_COLOR");
+    if !use_color(
+        no_color,
+        no_color_env.as_deref(),
+        io::stdout().is_terminal(),
+    ) {
+        colored::control::set_override(false);
+    }
 
     stderrlog::new()
         .module(module_path!())
@@ -41,128 +89,1342 @@ fn main() -> Result<()> {
     debug!("Creating dir structure...");
     dist_utils::path::create_dirs(dist_utils::Mode::Client)?;
 
+    // Everything else needs a config to work with
+    if let SubCommand::InitConfig = subcmd {
+        let config_path = dist_utils::path::client_config_file();
+        config::write_template(&config_path)?;
+        println!("Wrote a config template to {}", config_path.display());
+        return Ok(());
+    }
+
     let config = Config::try_new().context("Failed reading config file")?;
     debug!("Config: {:#?}", config);
+    let run_opts = RunOpts {
+        download_dir: download_dir.unwrap_or_else(dist_utils::path::torrent_data_dir),
+        yes,
+        dry_run,
+        progress,
+        interval,
+        seeding: config.seeding(),
+        confirm_threshold: config.confirm_threshold,
+    };
+
+    // Each server gets its own cached package database so that switching between them doesn't
+    // require syncing again
+    let server_urls: Vec<_> = match &server {
+        Some(server) => vec![server.base().to_owned()],
+        None => config
+            .server_urls
+            .as_slice()
+            .iter()
+            .map(|url| url.base().to_owned())
+            .collect(),
+    };
+    let cache_dir = cache_dir.unwrap_or_else(dist_utils::path::package_cache_dir);
+    let package_db_file = cache::db_file(
+        &cache_dir,
+        server_urls
+            .first()
+            .context("No server urls are listed in the config")?,
+    );
+    debug!("Package database: {}", package_db_file.display());
 
     match subcmd {
-        SubCommand::Sync => {
+        SubCommand::InitConfig => unreachable!("Handled before reading the config"),
+        SubCommand::Sync(SyncOpts { timeout }) => {
             // Get the latest package database
             println!("Attempting to sync the latest package database...");
-            let response = ureq::get(&format!("{}/packages.db", config.server_url)).call()?;
-            let mut db_file = BufWriter::new(File::create(&dist_utils::path::package_db_file())?);
-            let mut response_content = response.into_reader();
+            let mut agent_opts = config.agent_opts();
+            if let Some(timeout) = timeout {
+                agent_opts.timeout = Duration::from_secs(timeout);
+            }
+            let agent = agent_opts.build()?;
+            let (mirror, response) = sync::fetch_package_db(
+                &agent,
+                &server_urls,
+                config.retries,
+                sync::INITIAL_BACKOFF,
+                sync::stored_etag(&package_db_file).as_deref(),
+            )
+            .context(ClientError::SyncFailed)?;
+            if sync::is_not_modified(&response) {
+                println!("The package database is already up to date");
+                return Ok(());
+            }
+            println!("Syncing from {}...", mirror);
+            let etag = response.header("ETag").map(str::to_owned);
+            let mut content = Vec::new();
+            response
+                .into_reader()
+                .read_to_end(&mut content)
+                .context(ClientError::SyncFailed)?;
+
+            if let Some(public_key) = &config.public_key {
+                println!("Verifying the signature...");
+                let signature_url = config
+                    .signature_url
+                    .clone()
+                    .unwrap_or_else(|| format!("{}/packages.db.sig", mirror));
+                sync::verify_package_db(
+                    &agent,
+                    &content,
+                    public_key,
+                    &signature_url,
+                    config.retries,
+                    sync::INITIAL_BACKOFF,
+                )
+                .context(ClientError::SyncFailed)?;
+            }
 
             println!("Saving the file locally...");
-            io::copy(&mut response_content, &mut db_file)?;
-            db_file.flush()?;
+            fs::create_dir_all(&cache_dir)?;
+            sync::save_package_db(content.as_slice(), &package_db_file)?;
+            sync::store_etag(&package_db_file, etag.as_deref())?;
+            for evicted in cache::evict(&cache_dir, &package_db_file, config.max_cache_size)? {
+                debug!("Evicted cached database {}", evicted.display());
+            }
             println!("Finished syncing");
         }
-        SubCommand::Install(Package { name }) => {
-            // Get the entry for the package
-            let package_db = DistpacDB::connect(
-                &dist_utils::path::package_db_file(),
-                MissingDBAction::RaiseError,
-            )?;
-            let entry = package_db
-                .query(&name)?
-                .ok_or(anyhow::anyhow!("No package entry found for: {}", name))?;
+        SubCommand::Install(Packages { names, jobs }) => {
+            // Get the entries for the packages
+            let package_db = databases::open_package_db(&package_db_file)?;
+            let installed_db = databases::open_installed_db()?;
+            install(&package_db, &installed_db, &names, jobs, &run_opts)?;
+        }
+        SubCommand::Upgrade(Packages { names, jobs }) => {
+            let package_db = databases::open_package_db(&package_db_file)?;
+            let installed_db = databases::open_installed_db()?;
+            upgrade(&package_db, &installed_db, &names, jobs, &run_opts)?;
+        }
+        SubCommand::Outdated => {
+            let package_db = databases::open_package_db(&package_db_file)?;
+            let installed_db = databases::open_installed_db()?;
+            let (outdated, _) = resolve::find_outdated(&package_db, &installed_db, &[])?;
 
-            // Start downloading the package
-            println!("Downloading {}...", entry.torrent_name());
-            let mut transmission = Transmission::start(
-                TransmissionOpts::new().download_dir(dist_utils::path::torrent_data_dir()),
-            )?;
-            transmission.download_torrent(entry.magnet())?;
-
-            // Wait for the download to be done
-            let mut active = false;
-            let progress_bar = ProgressBar::new(*entry.size()).with_style(
-                ProgressStyle::default_bar()
-                    .template("[{wide_bar:.cyan}] {bytes}/{total_bytes} ({bytes_per_sec})")
-                    .progress_chars("=> "),
-            );
-            loop {
-                transmission.refresh()?;
-                if let Some(torrent) = transmission.get_by_name(entry.torrent_name()) {
-                    if torrent.is_finished() {
-                        progress_bar.finish_with_message("Finished downloading!");
-                        break;
-                    }
+            print!("{}", outdated_listing(&outdated));
+        }
+        SubCommand::Remove(Package { name }) => {
+            let installed_db = databases::open_installed_db()?;
+            let mut transmission = Transmission::start(transmission_opts(&run_opts.download_dir))?;
+            remove(&installed_db, &mut transmission, &name, &run_opts)?;
+        }
+        SubCommand::Clean => {
+            let installed_db = databases::open_installed_db()?;
+            let mut transmission = Transmission::start(transmission_opts(&run_opts.download_dir))?;
+            clean(&installed_db, &mut transmission, &run_opts)?;
+        }
+        SubCommand::List(ListOpts {
+            installed,
+            format,
+            limit,
+            offset,
+            sort,
+            reverse,
+        }) => {
+            // Either reads from the full database or installed database
+            let db = if installed {
+                databases::open_installed_db()?
+            } else {
+                databases::open_package_db(&package_db_file)?
+            };
+            let packages =
+                db.list_page(sort.into(), reverse, limit.unwrap_or(usize::MAX), offset)?;
 
-                    if *torrent.downloaded() != Bytes::zero() {
-                        // Just started the actual download so reset to display transfer speed
-                        // better
-                        if !active {
-                            progress_bar.reset();
-                            active = true;
-                        }
-                        progress_bar.set_position(f32::from(*torrent.downloaded()) as u64);
+            match format {
+                Format::Human => {
+                    print!("{}", package_table(&packages, !quiet));
+                    // Only worth mentioning when some of the packages might have been left out
+                    if !quiet && (limit.is_some() || offset > 0) {
+                        println!("Showing {} of {} packages", packages.len(), db.count()?);
                     }
                 }
+                Format::Json => println!("{}", serde_json::to_string_pretty(&packages)?),
+            }
+        }
+        SubCommand::Search(SearchQuery { query_terms }) => {
+            let package_db = databases::open_package_db(&package_db_file)?;
+            let packages = package_db.search(&query_terms)?;
+
+            print!("{}", package_table(&packages, !quiet));
+        }
+        SubCommand::Info(Package { name }) => {
+            let package_db = databases::open_package_db(&package_db_file)?;
+            let package = package_db
+                .query(&name)?
+                .ok_or(ClientError::PackageNotFound(name))?;
+
+            print!("{}", package_info(&package));
+        }
+        SubCommand::Verify(VerifyOpts { names, repair }) => {
+            let installed_db = databases::open_installed_db()?;
+            verify(&installed_db, &names, repair, &run_opts)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Settings shared by the commands that change what's installed
+struct RunOpts {
+    download_dir: PathBuf,
+    yes: bool,
+    dry_run: bool,
+    progress: ProgressFormat,
+    /// How long the download loop waits between refreshes
+    interval: Duration,
+    seeding: Seeding,
+    /// Upgrades only ask for confirmation once they go over this many bytes
+    confirm_threshold: u64,
+}
+
+fn install(
+    package_db: &DistpacDB,
+    installed_db: &DistpacDB,
+    names: &[String],
+    jobs: usize,
+    opts: &RunOpts,
+) -> Result<()> {
+    if names.is_empty() {
+        anyhow::bail!("No packages were listed to install");
+    }
+
+    let (entries, mut failures) = resolve::resolve_packages(package_db, installed_db, names);
+    // Dependencies get pulled in along with the listed packages, so they count towards the total
+    let total = entries.len() + failures.len();
+    if opts.dry_run {
+        for entry in &entries {
+            println!(
+                "Would add the torrent {} from {}",
+                entry.torrent_name(),
+                entry.magnet()
+            );
+            println!(
+                "Would add {} {} to the installed database",
+                entry.name(),
+                entry.version()
+            );
+        }
+
+        return report_failures("resolving", &failures, total);
+    }
+
+    if !entries.is_empty() && !prompt::confirm("install", &entries, opts.yes)? {
+        println!("Aborting");
+        return Ok(());
+    }
+
+    let downloaded = download_packages(entries, opts, jobs, &mut failures)?;
+    record_installed(installed_db, downloaded, &mut failures);
+
+    report_failures("installing", &failures, total)
+}
+
+fn upgrade(
+    package_db: &DistpacDB,
+    installed_db: &DistpacDB,
+    names: &[String],
+    jobs: usize,
+    opts: &RunOpts,
+) -> Result<()> {
+    let (upgrades, mut failures) = resolve::find_upgrades(package_db, installed_db, names)?;
+    if upgrades.is_empty() && failures.is_empty() {
+        println!("Everything is up to date");
+    }
+
+    let total = upgrades.len() + failures.len();
+    if opts.dry_run {
+        for entry in &upgrades {
+            println!(
+                "Would add the torrent {} from {}",
+                entry.torrent_name(),
+                entry.magnet()
+            );
+            println!(
+                "Would update {} to {} in the installed database",
+                entry.name(),
+                entry.version()
+            );
+        }
+
+        return report_failures("resolving", &failures, total);
+    }
+
+    if !upgrades.is_empty()
+        && !prompt::confirm_large("upgrade", &upgrades, opts.confirm_threshold, opts.yes)?
+    {
+        println!("Aborting");
+        return Ok(());
+    }
+
+    for entry in download_packages(upgrades, opts, jobs, &mut failures)? {
+        println!("Upgrading {} to {}...", entry.name(), entry.version());
+        let name = entry.name().to_owned();
+        if let Err(err) = installed_db.update_package_entry(entry) {
+            failures.push((name, err.into()));
+        }
+    }
 
-                thread::sleep(Duration::from_millis(200));
+    report_failures("upgrading", &failures, total)
+}
+
+fn remove(
+    installed_db: &DistpacDB,
+    torrents: &mut impl Torrents,
+    name: &str,
+    opts: &RunOpts,
+) -> Result<()> {
+    // Run the uninstall script first so that a failure leaves the package installed
+    if let Some(entry) = installed_db.query(name)? {
+        let package_dir = opts.download_dir.join(entry.torrent_name());
+        if opts.dry_run {
+            println!(
+                "Would run the uninstall script in {}",
+                package_dir.display()
+            );
+            println!("Would remove the torrent {}", entry.torrent_name());
+        } else {
+            if !prompt::confirm("remove", std::slice::from_ref(&entry), opts.yes)? {
+                println!("Aborting");
+                return Ok(());
             }
 
-            // FIXME: Permissions aren't set right for torrents so that would need to be fixed
-            // // Run the install script for the package
-            println!("Installing the package...");
-            // let script_location = dist_utils::path::torrent_data_dir()
-            //     .join(entry.torrent_name())
-            //     .join("scripts")
-            //     .join("install.sh");
-            // // TODO: handle the command returning an error code
-            // Command::new(script_location)
-            //     .stdout(Stdio::null())
-            //     .stderr(Stdio::null())
-            //     .status()?;
-
-            // Finally add the entry to the installed database
-            let installed_db = DistpacDB::connect(
-                &dist_utils::path::installed_db_file(),
-                MissingDBAction::Create,
-            )?;
-            installed_db.add_package_entry(entry)?;
+            torrents.refresh()?;
+            scripts::run_uninstall(&package_dir)
+                .with_context(|| format!("Failed uninstalling {}", name))?;
+
+            // The package is already uninstalled at this point, so this isn't worth failing over
+            let id = torrents
+                .get_by_name(entry.torrent_name())
+                .map(|torrent| *torrent.id());
+            if let Some(id) = id {
+                if let Err(err) = torrents.remove_torrent(id) {
+                    warn!(
+                        "Failed removing the torrent for {} Error: {}",
+                        entry.name(),
+                        err
+                    );
+                }
+            }
         }
-        SubCommand::Remove(Package { name }) => {
-            // TODO: this is done a lot. Would be nice to move it to some common code
-            let installed_db = DistpacDB::connect(
-                &dist_utils::path::installed_db_file(),
-                MissingDBAction::Create,
-            )?;
-            installed_db.remove_by_name(&name)?;
+    }
+
+    if opts.dry_run {
+        println!("Would remove {} from the installed database", name);
+    } else {
+        installed_db.remove_by_name(name)?;
+    }
 
-            // FIXME: Permissions aren't set right for torrents so that would need to be fixed
-            // TODO: run the uninstall script
+    Ok(())
+}
+
+// Removes the torrent data left behind by packages that aren't installed anymore
+fn clean(installed_db: &DistpacDB, torrents: &mut impl Torrents, opts: &RunOpts) -> Result<()> {
+    torrents.refresh()?;
+    let orphans = clean::orphaned(&opts.download_dir, installed_db, torrents)?;
+    if orphans.is_empty() {
+        println!("Nothing to clean");
+        return Ok(());
+    }
+
+    let mut items = Vec::new();
+    let mut total = 0;
+    for orphan in &orphans {
+        let size = clean::disk_usage(orphan)?;
+        total += size;
+        items.push(format!("{} ({})", orphan.display(), Bytes::from(size)));
+    }
+
+    if opts.dry_run {
+        for item in &items {
+            println!("Would remove {}", item);
         }
-        SubCommand::List(ListOpts { installed }) => {
-            // Either reads from the full database or installed database
-            let db = if installed {
-                DistpacDB::connect(
-                    &dist_utils::path::installed_db_file(),
-                    MissingDBAction::Create,
-                )
-            } else {
-                DistpacDB::connect(
-                    &dist_utils::path::package_db_file(),
-                    MissingDBAction::RaiseError,
-                )
-            }?;
-            let packages = db.list_all()?;
+        return Ok(());
+    }
+
+    if !prompt::confirm_items("Torrent data to remove", &items, total, opts.yes)? {
+        println!("Aborting");
+        return Ok(());
+    }
 
-            for package in packages {
-                display_package(&package);
+    for orphan in &orphans {
+        clean::remove(orphan).with_context(|| format!("Failed removing {}", orphan.display()))?;
+    }
+    println!("Freed {}", Bytes::from(total));
+
+    Ok(())
+}
+
+// Checks the installed packages against their checksums, having the torrent client fix up the
+// corrupt ones when repairing
+fn verify(installed_db: &DistpacDB, names: &[String], repair: bool, opts: &RunOpts) -> Result<()> {
+    let (installed, mut failures) = resolve::find_installed(installed_db, names)?;
+    let total = installed.len() + failures.len();
+    let mut corrupt = Vec::new();
+    for entry in installed {
+        match verify::check(&entry, &opts.download_dir) {
+            Ok(Integrity::Intact) => println!("{} {}", "OK".green().bold(), entry.name()),
+            Ok(Integrity::Unverified) => println!(
+                "{} {} (no checksum listed)",
+                "UNVERIFIED".yellow().bold(),
+                entry.name()
+            ),
+            Ok(Integrity::Corrupt) => {
+                println!("{} {}", "CORRUPT".red().bold(), entry.name());
+                corrupt.push(entry);
             }
+            Err(err) => failures.push((entry.name().to_owned(), err)),
         }
     }
 
+    // Repaired packages still count as failures since they're only fixed once the re-check is done
+    let repairing = repair && !corrupt.is_empty();
+    if repairing {
+        repair_packages(&corrupt, opts)?;
+    }
+    for entry in corrupt {
+        let err = anyhow::anyhow!("The downloaded data doesn't match the listed checksum");
+        let err = if repairing && !opts.dry_run {
+            err.context("A re-check was started")
+        } else {
+            err
+        };
+        failures.push((entry.name().to_owned(), err));
+    }
+
+    report_failures("verifying", &failures, total)
+}
+
+// Torrents that are still around get re-checked while ones that were removed get added again, which
+// has transmission check the existing data before downloading anything
+fn repair_packages(entries: &[PackageEntry], opts: &RunOpts) -> Result<()> {
+    if opts.dry_run {
+        for entry in entries {
+            println!("Would re-check the torrent {}", entry.torrent_name());
+        }
+        return Ok(());
+    }
+
+    let mut transmission = Transmission::start(transmission_opts(&opts.download_dir))?;
+    transmission.refresh()?;
+    for entry in entries {
+        println!("Repairing {}...", entry.name());
+        let id = transmission
+            .get_by_name(entry.torrent_name())
+            .map(|torrent| *torrent.id());
+        match id {
+            Some(id) => {
+                transmission.verify_torrent(id)?;
+                transmission.resume_torrent(id)?;
+            }
+            None => transmission.download_torrent(entry.magnet())?,
+        }
+    }
+    println!("Run `dist-client verify` again once the torrents finish downloading");
+
     Ok(())
 }
 
-fn display_package(package: &PackageEntry) {
-    println!(
-        "{}\t{}\t{}",
-        package.name().blue().bold(),
-        package.version().to_string().green().bold(),
-        pretty_bytes::converter::convert(*package.size() as f64).bold()
+/// Adds the downloaded packages to the installed database, skipping any whose dependencies failed
+fn record_installed(
+    installed_db: &DistpacDB,
+    downloaded: Vec<PackageEntry>,
+    failures: &mut Failures,
+) {
+    for entry in without_failed_dependencies(downloaded, failures) {
+        // FIXME: Permissions aren't set right for torrents so that would need to be fixed
+        // // Run the install script for the package
+        println!("Installing {}...", entry.name());
+        // let script_location = dist_utils::path::torrent_data_dir()
+        //     .join(entry.torrent_name())
+        //     .join("scripts")
+        //     .join("install.sh");
+        // // TODO: handle the command returning an error code
+        // Command::new(script_location)
+        //     .stdout(Stdio::null())
+        //     .stderr(Stdio::null())
+        //     .status()?;
+
+        // Finally add the entry to the installed database
+        let name = entry.name().to_owned();
+        if let Err(err) = installed_db.add_package_entry(entry) {
+            failures.push((name, err.into()));
+        }
+    }
+}
+
+/// Drops the entries that depend on a package that failed, directly or through another
+/// dependency, so that nothing gets recorded as installed without its dependencies
+///
+/// Dependencies come before their dependents, so checking in order catches the transitive ones too
+fn without_failed_dependencies(
+    entries: Vec<PackageEntry>,
+    failures: &mut Failures,
+) -> Vec<PackageEntry> {
+    let mut failed: HashSet<String> = failures.iter().map(|(name, _)| name.clone()).collect();
+    let mut remaining = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match entry
+            .dependencies()
+            .iter()
+            .find(|dependency| failed.contains(*dependency))
+        {
+            Some(dependency) => {
+                let err = anyhow::anyhow!("Dependency {} failed to install", dependency);
+                failed.insert(entry.name().to_owned());
+                failures.push((entry.name().to_owned(), err));
+            }
+            None => remaining.push(entry),
+        }
+    }
+
+    remaining
+}
+
+// Downloads and verifies each of the packages with a progress bar for each of them. Returns the
+// packages that are ready to be installed
+fn download_packages(
+    entries: Vec<PackageEntry>,
+    opts: &RunOpts,
+    jobs: usize,
+    failures: &mut Failures,
+) -> Result<Vec<PackageEntry>> {
+    if entries.is_empty() {
+        return Ok(entries);
+    }
+
+    let mut transmission = Transmission::start(transmission_opts(&opts.download_dir))?;
+    // `Ctrl-C` stops the downloads instead of leaving them running in the daemon. The usual
+    // handling comes back once the downloads are done
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler = signal_hook::flag::register(SIGINT, Arc::clone(&interrupted))?;
+    let downloaded = match opts.progress {
+        ProgressFormat::Bar => {
+            download_with_bars(&mut transmission, entries, jobs, opts, &interrupted)
+        }
+        ProgressFormat::Json => download::download_all(
+            &mut transmission,
+            entries,
+            &mut JsonEvents::new(io::stdout()),
+            jobs,
+            opts.interval,
+            &opts.seeding,
+            &interrupted,
+        ),
+    };
+    signal_hook::low_level::unregister(handler);
+    let (downloaded_entries, download_failures) = downloaded?;
+    failures.extend(download_failures);
+
+    // Make sure each package matches what was published before doing anything with it
+    let mut verified_entries = Vec::new();
+    for entry in downloaded_entries {
+        match verify_package(&entry, &opts.download_dir) {
+            Ok(()) => verified_entries.push(entry),
+            Err(err) => failures.push((
+                entry.name().to_owned(),
+                err.context(ClientError::DownloadFailed),
+            )),
+        }
+    }
+
+    Ok(verified_entries)
+}
+
+fn download_with_bars(
+    transmission: &mut Transmission,
+    entries: Vec<PackageEntry>,
+    jobs: usize,
+    opts: &RunOpts,
+    interrupted: &AtomicBool,
+) -> Result<(Vec<PackageEntry>, Failures)> {
+    let multi_bar = MultiProgress::new();
+    let style = ProgressStyle::default_bar()
+        .template("{msg} [{wide_bar:.cyan}] {bytes}/{total_bytes} ({bytes_per_sec})")
+        .progress_chars("=> ");
+    let mut bars: HashMap<_, _> = entries
+        .iter()
+        .map(|entry| {
+            let bar = multi_bar.add(ProgressBar::new(*entry.size()).with_style(style.clone()));
+            bar.set_message(entry.torrent_name());
+            (entry.torrent_name().to_owned(), bar)
+        })
+        .collect();
+    // The bars only get drawn while something is joined on them
+    let renderer = thread::spawn(move || multi_bar.join());
+
+    let downloaded = download::download_all(
+        transmission,
+        entries,
+        &mut bars,
+        jobs,
+        opts.interval,
+        &opts.seeding,
+        interrupted,
     );
+    // Anything left unfinished has to be wrapped up for the renderer to stop
+    for bar in bars.values().filter(|bar| !bar.is_finished()) {
+        bar.abandon();
+    }
+    if renderer.join().is_err() {
+        warn!("Drawing the progress bars panicked");
+    }
+
+    downloaded
+}
+
+fn transmission_opts(download_dir: &Path) -> TransmissionOpts {
+    TransmissionOpts::new()
+        .download_dir(download_dir.to_owned())
+        .labels(vec![TORRENT_LABEL.to_owned()])
+}
+
+// Reports everything that went wrong at the end so one bad package doesn't hold up the rest
+fn report_failures(action: &str, failures: &Failures, total: usize) -> Result<()> {
+    for (name, err) in failures {
+        eprintln!(
+            "{} {}: {:#}",
+            format!("Failed {}", action).red().bold(),
+            name,
+            err
+        );
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    // Keep the first failure's exit code around for scripts
+    let summary = format!("Failed {} {} of {} packages", action, failures.len(), total);
+    match failures.iter().find_map(|(_, err)| error::find(err)) {
+        Some(kind) => Err(anyhow::Error::new(kind.clone()).context(summary)),
+        None => Err(anyhow::anyhow!(summary)),
+    }
+}
+
+// BitTorrent already verifies each piece, but this guards against a malicious or stale listing
+fn verify_package(entry: &PackageEntry, data_dir: &Path) -> Result<()> {
+    let expected = match entry.sha256() {
+        Some(expected) => expected,
+        None => {
+            warn!(
+                "No checksum listed for {}. Skipping verification",
+                entry.name()
+            );
+            return Ok(());
+        }
+    };
+
+    let actual = checksum::sha256(&data_dir.join(entry.torrent_name()))
+        .with_context(|| format!("Failed hashing the downloaded data for {}", entry.name()))?;
+    if &actual != expected {
+        anyhow::bail!(
+            "Checksum mismatch for {}. Expected {} but the download hashed to {}",
+            entry.name(),
+            expected,
+            actual
+        );
+    }
+
+    Ok(())
+}
+
+fn package_info(package: &PackageEntry) -> String {
+    let mut fields = vec![
+        ("Name", package.name().to_owned()),
+        ("Version", package.version().to_string()),
+    ];
+    if let Some(description) = package.description() {
+        fields.push(("Description", description.to_owned()));
+    }
+    fields.extend(vec![
+        ("Size", Bytes::from(*package.size()).to_string()),
+        ("Torrent", package.torrent_name().to_owned()),
+        ("Magnet", package.magnet().to_owned()),
+    ]);
+
+    // Room for the longest label along with its colon
+    let width = fields
+        .iter()
+        .map(|(field, _)| field.len() + 1)
+        .max()
+        .unwrap_or(0);
+    fields
+        .iter()
+        .map(|(field, value)| {
+            format!(
+                "{} {}\n",
+                format!("{:<1$}", format!("{}:", field), width).bold(),
+                value
+            )
+        })
+        .collect()
+}
+
+// Colors are only kept for interactive terminals that haven't opted out through `--no-color` or a
+// non-empty `NO_COLOR`
+fn use_color(no_color: bool, no_color_env: Option<&OsStr>, stdout_is_tty: bool) -> bool {
+    let env_opt_out = no_color_env.is_some_and(|value| !value.is_empty());
+    stdout_is_tty && !no_color && !env_opt_out
+}
+
+// Each package's name followed by the installed version and the newer one, e.g.
+// `ripgrep  12.1.0 -> 12.1.1`
+fn outdated_listing(outdated: &[Outdated]) -> String {
+    let width = outdated
+        .iter()
+        .map(|outdated| outdated.installed.name().len())
+        .max()
+        .unwrap_or_default();
+
+    let mut listing = String::new();
+    for Outdated {
+        installed,
+        available,
+    } in outdated
+    {
+        listing.push_str(&format!(
+            "{}  {} -> {}\n",
+            format!("{:<1$}", installed.name(), width).blue().bold(),
+            installed.version(),
+            available.version().to_string().green().bold()
+        ));
+    }
+
+    listing
+}
+
+// Lines the packages up in columns of name, version, and size, along with the description when any
+// of the packages have one
+fn package_table(packages: &[PackageEntry], header: bool) -> String {
+    let described = packages
+        .iter()
+        .any(|package| package.description().is_some());
+    let rows: Vec<_> = packages
+        .iter()
+        .map(|package| {
+            [
+                package.name().to_owned(),
+                package.version().to_string(),
+                Bytes::from(*package.size()).to_string(),
+                package.description().clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let headers = ["Name", "Version", "Size", "Description"];
+
+    // Widths are based off the plain text since the escape codes for coloring take up no space
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    if header {
+        for (width, cell) in widths.iter_mut().zip(headers.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    if header {
+        table.push_str(&format!(
+            "{}  {}  ",
+            format!("{:<1$}", headers[0], widths[0]).underline(),
+            format!("{:<1$}", headers[1], widths[1]).underline(),
+        ));
+        if described {
+            table.push_str(&format!(
+                "{}  {}\n",
+                format!("{:<1$}", headers[2], widths[2]).underline(),
+                headers[3].underline()
+            ));
+        } else {
+            table.push_str(&format!("{}\n", headers[2].underline()));
+        }
+    }
+    for [name, version, size, description] in rows {
+        table.push_str(&format!(
+            "{}  {}  ",
+            format!("{:<1$}", name, widths[0]).blue().bold(),
+            format!("{:<1$}", version, widths[1]).green().bold(),
+        ));
+        if !description.is_empty() {
+            table.push_str(&format!(
+                "{}  {}\n",
+                format!("{:<1$}", size, widths[2]).bold(),
+                description
+            ));
+        } else {
+            table.push_str(&format!("{}\n", size.bold()));
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dist_package::manifest::Version;
+    use dist_package_db::database::MissingDBAction;
+
+    use crate::test_utils::FakeTorrents;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    // Empty package and installed databases in a temp dir
+    fn test_dbs() -> BoxResult<(tempfile::TempDir, DistpacDB, DistpacDB)> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        let installed_db = DistpacDB::connect(
+            &temp_dir.path().join("installed.db"),
+            MissingDBAction::Create,
+        )?;
+
+        Ok((temp_dir, package_db, installed_db))
+    }
+
+    // Options that never stop to ask or wait around
+    fn test_opts(download_dir: PathBuf) -> RunOpts {
+        RunOpts {
+            download_dir,
+            yes: true,
+            dry_run: false,
+            progress: ProgressFormat::Bar,
+            interval: Duration::from_millis(1),
+            seeding: Seeding::default(),
+            confirm_threshold: 0,
+        }
+    }
+
+    #[test]
+    fn download_size() -> BoxResult<()> {
+        let (_temp_dir, package_db, installed_db) = test_dbs()?;
+        for (name, size, dependencies) in &[
+            ("ripgrep", 1_500_000, vec!["pcre2".to_owned()]),
+            ("pcre2", 500_000, Vec::new()),
+            ("fd", 250_000, Vec::new()),
+        ] {
+            package_db.add_package_entry(
+                PackageEntry::new(
+                    name.to_string(),
+                    Version::new(1, 0, 0),
+                    format!("magnet:?xt=urn:btih:{}", name),
+                    *size,
+                )
+                .with_dependencies(dependencies.clone()),
+            )?;
+        }
+
+        // Dependencies count towards the total too
+        let (entries, failures) =
+            resolve::resolve_packages(&package_db, &installed_db, &["ripgrep".to_owned()]);
+        assert!(failures.is_empty());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(prompt::total_size(&entries), 2_000_000);
+
+        Ok(())
+    }
+
+    fn names(packages: &[PackageEntry]) -> Vec<&str> {
+        packages
+            .iter()
+            .map(|package| package.name().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn failed_dependency() -> BoxResult<()> {
+        let entry = |name: &str, dependencies: &[&str]| {
+            PackageEntry::new(
+                name.to_owned(),
+                Version::new(1, 0, 0),
+                format!("magnet:?xt=urn:btih:{}", name),
+                1_000,
+            )
+            .with_dependencies(dependencies.iter().map(|dep| dep.to_string()).collect())
+        };
+
+        // `b` failed to download, which takes `a` down with it and then `app` through `a`
+        let downloaded = vec![entry("a", &["b"]), entry("fd", &[]), entry("app", &["a"])];
+        let mut failures: Failures = vec![("b".to_owned(), anyhow::anyhow!("Download failed"))];
+        let (_temp_dir, _package_db, installed_db) = test_dbs()?;
+        record_installed(&installed_db, downloaded, &mut failures);
+        assert_eq!(names(&installed_db.list_all()?), ["fd"]);
+        let failed: Vec<_> = failures.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(failed, ["b", "a", "app"]);
+        assert_eq!(failures[1].1.to_string(), "Dependency b failed to install");
+
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run() -> BoxResult<()> {
+        let (temp_dir, package_db, installed_db) = test_dbs()?;
+        for (name, version) in &[
+            ("ripgrep", Version::new(12, 0, 0)),
+            ("fd", Version::new(8, 0, 0)),
+        ] {
+            package_db.add_package_entry(PackageEntry::new(
+                name.to_string(),
+                *version,
+                format!("magnet:?xt=urn:btih:{}", name),
+                1_000,
+            ))?;
+        }
+        installed_db.add_package_entry(PackageEntry::new(
+            "fd".to_owned(),
+            Version::new(7, 0, 0),
+            "magnet:?xt=urn:btih:fd".to_owned(),
+            1_000,
+        ))?;
+        let opts = RunOpts {
+            dry_run: true,
+            ..test_opts(temp_dir.path().join("data"))
+        };
+
+        // None of these should touch the installed database
+        install(
+            &package_db,
+            &installed_db,
+            &["ripgrep".to_owned()],
+            4,
+            &opts,
+        )?;
+        upgrade(&package_db, &installed_db, &[], 4, &opts)?;
+        let fd = installed_db.query("fd")?.ok_or("fd isn't installed")?;
+        let mut torrents = FakeTorrents::new(&[]).with_existing(&fd, 1);
+        remove(&installed_db, &mut torrents, "fd", &opts)?;
+        let installed = installed_db.list_all()?;
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].version(), &Version::new(7, 0, 0));
+        assert!(torrents.get_by_name("fd-7.0.0").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_torrent() -> BoxResult<()> {
+        let (temp_dir, _, installed_db) = test_dbs()?;
+        let data_dir = temp_dir.path().join("data");
+        let (fd, ripgrep) = (
+            PackageEntry::new(
+                "fd".to_owned(),
+                Version::new(7, 0, 0),
+                "magnet:?xt=urn:btih:fd".to_owned(),
+                1_000,
+            ),
+            PackageEntry::new(
+                "ripgrep".to_owned(),
+                Version::new(12, 1, 1),
+                "magnet:?xt=urn:btih:ripgrep".to_owned(),
+                1_000,
+            ),
+        );
+        let mut torrents = FakeTorrents::new(&[])
+            .with_existing(&fd, 1)
+            .with_existing(&ripgrep, 1);
+        installed_db.add_package_entries(vec![fd, ripgrep])?;
+        let scripts_dir = data_dir.join("ripgrep-12.1.1").join("scripts");
+        fs::create_dir_all(&scripts_dir)?;
+        fs::write(
+            scripts_dir.join("uninstall.sh"),
+            "#!/bin/sh
+exit 1
+",
+        )?;
+        let opts = test_opts(data_dir);
+
+        // Only the removed package's torrent goes away
+        remove(&installed_db, &mut torrents, "fd", &opts)?;
+        assert!(torrents.get_by_name("fd-7.0.0").is_none());
+        assert!(torrents.get_by_name("ripgrep-12.1.1").is_some());
+        assert_eq!(names(&installed_db.list_all()?), ["ripgrep"]);
+
+        // A failed uninstall keeps the torrent around along with the package
+        assert!(remove(&installed_db, &mut torrents, "ripgrep", &opts).is_err());
+        assert!(torrents.get_by_name("ripgrep-12.1.1").is_some());
+        assert_eq!(names(&installed_db.list_all()?), ["ripgrep"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_package_exit_code() -> BoxResult<()> {
+        let (temp_dir, package_db, installed_db) = test_dbs()?;
+        let opts = test_opts(temp_dir.path().join("data"));
+
+        let err = install(
+            &package_db,
+            &installed_db,
+            &["missing".to_owned()],
+            4,
+            &opts,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error::find(&err),
+            Some(&ClientError::PackageNotFound("missing".to_owned()))
+        );
+        assert_eq!(error::exit_code(&err), 3);
+        assert_eq!(err.to_string(), "Failed installing 1 of 1 packages");
+
+        Ok(())
+    }
+
+    #[test]
+    fn repair_exit_code() -> BoxResult<()> {
+        let (temp_dir, _, installed_db) = test_dbs()?;
+        // The data was never downloaded, so it can't match
+        installed_db.add_package_entry(
+            PackageEntry::new(
+                "ripgrep".to_owned(),
+                Version::new(12, 1, 1),
+                "magnet:?xt=urn:btih:ripgrep".to_owned(),
+                1_000,
+            )
+            .with_sha256("0".repeat(64)),
+        )?;
+        let opts = RunOpts {
+            dry_run: true,
+            ..test_opts(temp_dir.path().join("data"))
+        };
+
+        for &repair in &[false, true] {
+            let err = verify(&installed_db, &[], repair, &opts).unwrap_err();
+            assert_eq!(err.to_string(), "Failed verifying 1 of 1 packages");
+            assert_eq!(error::exit_code(&err), 1);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn install_failure_total() -> BoxResult<()> {
+        let (temp_dir, package_db, installed_db) = test_dbs()?;
+        for (name, dependencies) in &[("app", vec!["lib".to_owned()]), ("lib", Vec::new())] {
+            package_db.add_package_entry(
+                PackageEntry::new(
+                    name.to_string(),
+                    Version::new(1, 0, 0),
+                    format!("magnet:?xt=urn:btih:{}", name),
+                    1_000,
+                )
+                .with_dependencies(dependencies.clone()),
+            )?;
+        }
+        let opts = RunOpts {
+            dry_run: true,
+            ..test_opts(temp_dir.path().join("data"))
+        };
+
+        // The dependency that got pulled in counts too
+        let err = install(
+            &package_db,
+            &installed_db,
+            &["app".to_owned(), "missing".to_owned()],
+            4,
+            &opts,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Failed resolving 1 of 3 packages");
+
+        Ok(())
+    }
+
+    #[test]
+    fn clean_orphans() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let data_dir = temp_dir.path().join("data");
+        for dir in &[
+            "ripgrep-12.1.1",
+            "ripgrep-12.0.0",
+            "removed-1.0.0",
+            "seeding-2.0.0",
+        ] {
+            fs::create_dir_all(data_dir.join(dir).join("assets"))?;
+            fs::write(data_dir.join(dir).join("assets").join("bin"), [0; 100])?;
+        }
+        fs::write(data_dir.join("single-file-1.0.0"), [0; 50])?;
+        // Someone else's file in a shared download dir
+        fs::write(data_dir.join("notes.txt"), [0; 10])?;
+        let installed_db = DistpacDB::connect(
+            &temp_dir.path().join("installed.db"),
+            MissingDBAction::Create,
+        )?;
+        installed_db.add_package_entry(PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(12, 1, 1),
+            "magnet:?xt=urn:btih:ripgrep".to_owned(),
+            100,
+        ))?;
+        // Removed from the installed database, but transmission is still seeding it
+        let seeding = PackageEntry::new(
+            "seeding".to_owned(),
+            Version::new(2, 0, 0),
+            "magnet:?xt=urn:btih:seeding".to_owned(),
+            100,
+        );
+        let mut torrents = FakeTorrents::new(&[]).with_existing(&seeding, 1);
+
+        let orphans = clean::orphaned(&data_dir, &installed_db, &torrents)?;
+        assert_eq!(
+            orphans,
+            [
+                data_dir.join("removed-1.0.0"),
+                data_dir.join("ripgrep-12.0.0"),
+                data_dir.join("single-file-1.0.0"),
+            ]
+        );
+        assert_eq!(clean::disk_usage(&data_dir)?, 460);
+
+        let mut opts = RunOpts {
+            dry_run: true,
+            ..test_opts(data_dir.clone())
+        };
+        clean(&installed_db, &mut torrents, &opts)?;
+        assert_eq!(fs::read_dir(&data_dir)?.count(), 6);
+
+        opts.dry_run = false;
+        clean(&installed_db, &mut torrents, &opts)?;
+        let mut remaining: Vec<_> = fs::read_dir(&data_dir)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<Result<_, _>>()?;
+        remaining.sort();
+        assert_eq!(remaining, ["notes.txt", "ripgrep-12.1.1", "seeding-2.0.0"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn download_dir_override() -> BoxResult<()> {
+        let Opts { download_dir, .. } = Opts::try_parse_from([
+            "distpac",
+            "--download-dir",
+            "/tmp/packages",
+            "install",
+            "fd",
+        ])?;
+        let download_dir = download_dir.ok_or("Missing download dir")?;
+        assert_eq!(
+            transmission_opts(&download_dir).download_dir,
+            Some(PathBuf::from("/tmp/packages"))
+        );
+
+        // The flag is global so it can come after the subcommand too
+        let Opts { download_dir, .. } =
+            Opts::try_parse_from(["distpac", "install", "fd", "--download-dir", "/tmp/other"])?;
+        assert_eq!(download_dir, Some(PathBuf::from("/tmp/other")));
+        let Opts { download_dir, .. } = Opts::try_parse_from(["distpac", "install", "fd"])?;
+        assert_eq!(download_dir, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn poll_interval() -> BoxResult<()> {
+        let Opts { interval, .. } = Opts::try_parse_from(["distpac", "install", "fd"])?;
+        assert_eq!(interval, Duration::from_millis(200));
+        let Opts { interval, .. } =
+            Opts::try_parse_from(["distpac", "install", "fd", "--interval", "1000"])?;
+        assert_eq!(interval, Duration::from_secs(1));
+
+        for bad in &["10", "6000", "fast"] {
+            assert!(Opts::try_parse_from(["distpac", "--interval", bad, "install", "fd"]).is_err());
+        }
+
+        Ok(())
+    }
+
+    // Drops the escape codes used for coloring
+    fn strip_colors(s: &str) -> String {
+        let mut stripped = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(c);
+            }
+        }
+
+        stripped
+    }
+
+    #[test]
+    fn aligned_table() {
+        let packages = [
+            PackageEntry::new(
+                "fd".to_owned(),
+                Version::new(8, 2, 1),
+                "magnet:?xt=urn:btih:fd".to_owned(),
+                500_000,
+            ),
+            PackageEntry::new(
+                "rust-analyzer".to_owned(),
+                Version::new(10, 12, 100),
+                "magnet:?xt=urn:btih:rust-analyzer".to_owned(),
+                45_000_000,
+            ),
+        ];
+
+        let table = strip_colors(&package_table(&packages, true));
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Name           Version    Size");
+        assert_eq!(lines[1], "fd             8.2.1      500.00 kB");
+        assert_eq!(lines[2], "rust-analyzer  10.12.100  45.00 MB");
+
+        // Quiet output skips the header
+        let table = strip_colors(&package_table(&packages, false));
+        assert_eq!(table.lines().count(), 2);
+        assert!(table.starts_with("fd             8.2.1      500.00 kB"));
+
+        // Descriptions get their own column when there are any
+        let [fd, rust_analyzer] = packages;
+        let packages = [fd.with_description("A fast find".to_owned()), rust_analyzer];
+        let table = strip_colors(&package_table(&packages, true));
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines[0], "Name           Version    Size       Description");
+        assert_eq!(lines[1], "fd             8.2.1      500.00 kB  A fast find");
+        assert_eq!(lines[2], "rust-analyzer  10.12.100  45.00 MB");
+    }
+
+    #[test]
+    fn outdated_versions() {
+        let entry = |name: &str, version| {
+            PackageEntry::new(
+                name.to_owned(),
+                version,
+                format!("magnet:?xt=urn:btih:{}", name),
+                1_000,
+            )
+        };
+        let outdated = [
+            Outdated {
+                installed: entry("fd", Version::new(8, 2, 0)),
+                available: entry("fd", Version::new(8, 2, 1)),
+            },
+            Outdated {
+                installed: entry("ripgrep", Version::new(1, 0, 0)),
+                available: entry("ripgrep", Version::new(1, 1, 0)),
+            },
+        ];
+
+        assert_eq!(
+            strip_colors(&outdated_listing(&outdated)),
+            "fd       8.2.0 -> 8.2.1\nripgrep  1.0.0 -> 1.1.0\n"
+        );
+    }
+
+    #[test]
+    fn no_color() {
+        let tty = true;
+        assert!(use_color(false, None, tty));
+        assert!(use_color(false, Some(OsStr::new("")), tty));
+        assert!(!use_color(true, None, tty));
+        assert!(!use_color(false, Some(OsStr::new("1")), tty));
+        assert!(!use_color(false, None, !tty));
+
+        colored::control::set_override(false);
+        let packages = [PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(12, 1, 1),
+            "magnet:?xt=urn:btih:ripgrep".to_owned(),
+            1_500_000,
+        )];
+        let table = package_table(&packages, true);
+        colored::control::unset_override();
+        assert!(!table.contains('\x1b'), "Found escape codes in {:?}", table);
+        assert!(table.contains("ripgrep"));
+    }
+
+    #[test]
+    fn json_list() -> BoxResult<()> {
+        let packages = [PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(12, 1, 1),
+            "magnet:?xt=urn:btih:ripgrep".to_owned(),
+            1_500_000,
+        )
+        .with_dependencies(vec!["pcre2".to_owned()])];
+
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&packages)?)?;
+        let listed = &json[0];
+        assert_eq!(listed["name"], "ripgrep");
+        assert_eq!(listed["version"], "12.1.1");
+        assert_eq!(listed["torrent_name"], "ripgrep-12.1.1");
+        assert_eq!(listed["magnet"], "magnet:?xt=urn:btih:ripgrep");
+        assert_eq!(listed["size"], 1_500_000);
+        assert_eq!(listed["sha256"], serde_json::Value::Null);
+        assert_eq!(listed["dependencies"][0], "pcre2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn info_block() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        package_db.add_package_entry(
+            PackageEntry::new(
+                "ripgrep".to_owned(),
+                Version::new(12, 1, 1),
+                "magnet:?xt=urn:btih:ripgrep".to_owned(),
+                1_500_000,
+            )
+            .with_description("Recursively search directories".to_owned()),
+        )?;
+
+        let package = package_db.query("ripgrep")?.ok_or("Missing package")?;
+        let info = package_info(&package);
+        for expected in [
+            "ripgrep",
+            "Recursively search directories",
+            "12.1.1",
+            "1.50 MB",
+            "ripgrep-12.1.1",
+            "magnet:?xt=urn:btih:ripgrep",
+        ]
+        .iter()
+        {
+            assert!(info.contains(expected), "Missing {} in {}", expected, info);
+        }
+        assert!(package_db.query("missing")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn checksum_mismatch() -> BoxResult<()> {
+        let data_dir = tempfile::tempdir()?;
+        let entry = PackageEntry::new(
+            "package".to_owned(),
+            Version::new(1, 0, 0),
+            "magnet:?xt=urn:btih:package".to_owned(),
+            1_000,
+        );
+        let package_dir = data_dir.path().join(entry.torrent_name());
+        fs::create_dir(&package_dir)?;
+        fs::write(package_dir.join("manifest.yaml"), "name: package\n")?;
+
+        // Nothing to check against for older listings
+        verify_package(&entry, data_dir.path())?;
+
+        let entry = entry.with_sha256(checksum::sha256(&package_dir)?);
+        verify_package(&entry, data_dir.path())?;
+
+        fs::write(package_dir.join("manifest.yaml"), "name: tampered\n")?;
+        let err = verify_package(&entry, data_dir.path()).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+
+        Ok(())
+    }
 }
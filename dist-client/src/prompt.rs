@@ -0,0 +1,275 @@
+use anyhow::Result;
+use dist_package_db::models::PackageEntry;
+use transmission_wrapper::bytes::Bytes;
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Lists the affected packages and asks whether to go ahead with `action`
+///
+/// `assume_yes` skips the prompt entirely, and anything other than an interactive terminal is
+/// treated as a no so that scripts don't end up hanging
+pub fn confirm(action: &str, packages: &[PackageEntry], assume_yes: bool) -> Result<bool> {
+    let (items, total) = package_items(packages);
+    confirm_items(
+        &format!("Packages to {}", action),
+        &items,
+        total,
+        assume_yes,
+    )
+}
+
+/// Like [`confirm`], but only asks once the combined size of the packages goes over `threshold`
+pub fn confirm_large(
+    action: &str,
+    packages: &[PackageEntry],
+    threshold: u64,
+    assume_yes: bool,
+) -> Result<bool> {
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    confirm_large_with(
+        action,
+        packages,
+        threshold,
+        assume_yes,
+        interactive,
+        &mut stdin.lock(),
+        &mut io::stdout(),
+    )
+}
+
+fn confirm_large_with(
+    action: &str,
+    packages: &[PackageEntry],
+    threshold: u64,
+    assume_yes: bool,
+    interactive: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<bool> {
+    let (items, total) = package_items(packages);
+    if total <= threshold {
+        writeln!(output, "Total size: {}", Bytes::from(total))?;
+        return Ok(true);
+    }
+
+    confirm_with(
+        &format!("Packages to {}", action),
+        &items,
+        total,
+        assume_yes,
+        interactive,
+        input,
+        output,
+    )
+}
+
+/// The combined size of all of the packages
+pub fn total_size(packages: &[PackageEntry]) -> u64 {
+    packages.iter().map(|package| *package.size()).sum()
+}
+
+/// Like [`confirm`], but for anything that can be listed out with a total size
+pub fn confirm_items(
+    heading: &str,
+    items: &[String],
+    total: u64,
+    assume_yes: bool,
+) -> Result<bool> {
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    confirm_with(
+        heading,
+        items,
+        total,
+        assume_yes,
+        interactive,
+        &mut stdin.lock(),
+        &mut io::stdout(),
+    )
+}
+
+fn package_items(packages: &[PackageEntry]) -> (Vec<String>, u64) {
+    let items = packages
+        .iter()
+        .map(|package| {
+            format!(
+                "{} {} ({})",
+                package.name(),
+                package.version(),
+                Bytes::from(*package.size())
+            )
+        })
+        .collect();
+    (items, total_size(packages))
+}
+
+fn confirm_with(
+    heading: &str,
+    items: &[String],
+    total: u64,
+    assume_yes: bool,
+    interactive: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<bool> {
+    writeln!(output, "{}:", heading)?;
+    for item in items {
+        writeln!(output, "    {}", item)?;
+    }
+    writeln!(output, "Total size: {}", Bytes::from(total))?;
+
+    if assume_yes {
+        return Ok(true);
+    }
+
+    if !interactive {
+        writeln!(
+            output,
+            "Not running interactively. Pass `--yes` to skip confirming"
+        )?;
+        return Ok(false);
+    }
+
+    write!(output, "Proceed? [y/N] ")?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dist_package::manifest::Version;
+
+    fn packages() -> Vec<PackageEntry> {
+        vec![
+            PackageEntry::new(
+                "ripgrep".to_owned(),
+                Version::new(12, 1, 1),
+                "magnet:?xt=urn:btih:ripgrep".to_owned(),
+                1_500_000,
+            ),
+            PackageEntry::new(
+                "fd".to_owned(),
+                Version::new(8, 2, 1),
+                "magnet:?xt=urn:btih:fd".to_owned(),
+                500_000,
+            ),
+        ]
+    }
+
+    // Input that fails the test if anything tries to read from it
+    struct NoInput;
+
+    impl io::Read for NoInput {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            panic!("Tried reading input");
+        }
+    }
+
+    impl BufRead for NoInput {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            panic!("Tried reading input");
+        }
+
+        fn consume(&mut self, _: usize) {}
+    }
+
+    #[test]
+    fn assume_yes() -> Result<()> {
+        let mut output = Vec::new();
+        let (items, total) = package_items(&packages());
+        assert!(confirm_with(
+            "Packages to install",
+            &items,
+            total,
+            true,
+            true,
+            &mut NoInput,
+            &mut output
+        )?);
+
+        let output = String::from_utf8(output)?;
+        assert!(output.contains("ripgrep 12.1.1 (1.50 MB)"));
+        assert!(output.contains("Total size: 2.00 MB"));
+        assert!(!output.contains("Proceed?"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn non_interactive() -> Result<()> {
+        let mut output = Vec::new();
+        let (items, total) = package_items(&packages());
+        assert!(!confirm_with(
+            "Packages to remove",
+            &items,
+            total,
+            false,
+            false,
+            &mut NoInput,
+            &mut output
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn answers() -> Result<()> {
+        let (items, total) = package_items(&packages());
+        let answer = |input: &str| {
+            confirm_with(
+                "Packages to install",
+                &items,
+                total,
+                false,
+                true,
+                &mut input.as_bytes(),
+                &mut io::sink(),
+            )
+        };
+
+        assert!(answer("y\n")?);
+        assert!(answer("Yes\n")?);
+        assert!(!answer("n\n")?);
+        assert!(!answer("\n")?);
+        assert!(!answer("")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn size_threshold() -> Result<()> {
+        let large = |threshold, assume_yes, output: &mut Vec<u8>| {
+            confirm_large_with(
+                "upgrade",
+                &packages(),
+                threshold,
+                assume_yes,
+                false,
+                &mut NoInput,
+                output,
+            )
+        };
+
+        // Staying under the threshold goes ahead without asking
+        let mut output = Vec::new();
+        assert!(large(2_000_000, false, &mut output)?);
+        assert_eq!(String::from_utf8(output)?, "Total size: 2.00 MB\n");
+
+        // Going over it has to be confirmed, which can't happen without a terminal
+        let mut output = Vec::new();
+        assert!(!large(1_999_999, false, &mut output)?);
+        assert!(String::from_utf8(output)?.contains("Pass `--yes` to skip confirming"));
+
+        // Unless `--yes` was passed
+        let mut output = Vec::new();
+        assert!(large(1_999_999, true, &mut output)?);
+        assert!(String::from_utf8(output)?.contains("Packages to upgrade:"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::Result;
+use dist_package::spec::{PackageSpec, VersionReq};
+use dist_package_db::{database::DistpacDB, models::PackageEntry};
+
+use crate::error::ClientError;
+
+pub type Failures = Vec<(String, anyhow::Error)>;
+
+/// Looks up each package spec (e.g. `name`, `name@1.2.3`, or `name>=1.2.0`) along with any of its
+/// dependencies that aren't installed yet
+///
+/// The entries are ordered so that dependencies come before the packages that need them. Packages
+/// that couldn't be resolved, either from a missing entry or a dependency cycle, are returned as
+/// failures without holding up the others
+pub fn resolve_packages(
+    package_db: &DistpacDB,
+    installed_db: &DistpacDB,
+    names: &[String],
+) -> (Vec<PackageEntry>, Failures) {
+    let mut entries = Vec::new();
+    let mut failures = Vec::new();
+
+    for name in names {
+        let mut resolver = Resolver {
+            package_db,
+            installed_db,
+            resolved: &entries,
+            tree: Vec::new(),
+            path: Vec::new(),
+        };
+
+        let resolved = name
+            .parse::<PackageSpec>()
+            .map_err(anyhow::Error::from)
+            .and_then(|spec| resolver.resolve(&spec));
+        match resolved {
+            Ok(()) => {
+                let tree = resolver.tree;
+                entries.extend(tree);
+            }
+            Err(err) => failures.push((name.to_owned(), err)),
+        }
+    }
+
+    (entries, failures)
+}
+
+/// Looks up the installed packages, or all of them if no names are given
+pub fn find_installed(
+    installed_db: &DistpacDB,
+    names: &[String],
+) -> Result<(Vec<PackageEntry>, Failures)> {
+    if names.is_empty() {
+        return Ok((installed_db.list_all()?, Vec::new()));
+    }
+
+    let mut installed = Vec::new();
+    let mut failures = Vec::new();
+    for name in names {
+        match installed_db.query(name)? {
+            Some(entry) => installed.push(entry),
+            None => failures.push((name.to_owned(), anyhow::anyhow!("{} isn't installed", name))),
+        }
+    }
+
+    Ok((installed, failures))
+}
+
+/// An installed package along with the newer version that's listed
+pub struct Outdated {
+    pub installed: PackageEntry,
+    pub available: PackageEntry,
+}
+
+/// Finds the installed packages that have a newer version listed, or all of them if no names are
+/// given
+pub fn find_outdated(
+    package_db: &DistpacDB,
+    installed_db: &DistpacDB,
+    names: &[String],
+) -> Result<(Vec<Outdated>, Failures)> {
+    let (installed, failures) = find_installed(installed_db, names)?;
+
+    let mut outdated = Vec::new();
+    for entry in installed {
+        match package_db.query(entry.name())? {
+            Some(latest) if latest.version() > entry.version() => outdated.push(Outdated {
+                installed: entry,
+                available: latest,
+            }),
+            // Either it's already up to date or it's no longer listed
+            _ => {}
+        }
+    }
+
+    Ok((outdated, failures))
+}
+
+/// Finds the newer versions of the installed packages, or all of them if no names are given
+pub fn find_upgrades(
+    package_db: &DistpacDB,
+    installed_db: &DistpacDB,
+    names: &[String],
+) -> Result<(Vec<PackageEntry>, Failures)> {
+    let (outdated, failures) = find_outdated(package_db, installed_db, names)?;
+    let upgrades = outdated
+        .into_iter()
+        .map(|outdated| outdated.available)
+        .collect();
+
+    Ok((upgrades, failures))
+}
+
+struct Resolver<'a> {
+    package_db: &'a DistpacDB,
+    installed_db: &'a DistpacDB,
+    // Packages resolved for previously requested packages
+    resolved: &'a [PackageEntry],
+    // Packages resolved for the currently requested package
+    tree: Vec<PackageEntry>,
+    // The chain of packages that led to the current one, used to detect cycles
+    path: Vec<String>,
+}
+
+impl Resolver<'_> {
+    // Depth-first so that dependencies get pushed before their dependents
+    fn resolve(&mut self, spec: &PackageSpec) -> Result<()> {
+        let name = spec.name.as_str();
+        let already_resolved = self
+            .resolved
+            .iter()
+            .chain(self.tree.iter())
+            .any(|entry| entry.name() == name);
+        if already_resolved {
+            return Ok(());
+        }
+
+        if self.path.iter().any(|seen| seen == name) {
+            anyhow::bail!(
+                "Dependency cycle detected: {} -> {}",
+                self.path.join(" -> "),
+                name
+            );
+        }
+
+        let entry = self
+            .package_db
+            .query_version(name, spec.req)?
+            .ok_or_else(|| match spec.req {
+                VersionReq::Any => ClientError::PackageNotFound(name.to_owned()),
+                req => ClientError::NoMatchingVersion(name.to_owned(), req),
+            })?;
+
+        self.path.push(name.to_owned());
+        for dependency in entry.dependencies() {
+            if self.installed_db.query(dependency)?.is_none() {
+                self.resolve(&PackageSpec::any(dependency.to_owned()))?;
+            }
+        }
+        self.path.pop();
+
+        self.tree.push(entry);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dist_package::manifest::Version;
+    use dist_package_db::database::MissingDBAction;
+    use tempfile::TempDir;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    // Each package is listed alongside its dependencies
+    fn seeded_db(
+        temp_dir: &TempDir,
+        file: &str,
+        packages: &[(&str, &[&str])],
+    ) -> BoxResult<DistpacDB> {
+        let versioned: Vec<_> = packages
+            .iter()
+            .map(|(name, dependencies)| (*name, Version::new(1, 0, 0), *dependencies))
+            .collect();
+        seeded_versioned_db(temp_dir, file, &versioned)
+    }
+
+    fn seeded_versioned_db(
+        temp_dir: &TempDir,
+        file: &str,
+        packages: &[(&str, Version, &[&str])],
+    ) -> BoxResult<DistpacDB> {
+        let db = DistpacDB::connect(&temp_dir.path().join(file), MissingDBAction::Create)?;
+        for (name, version, dependencies) in packages {
+            db.add_package_entry(
+                PackageEntry::new(
+                    name.to_string(),
+                    *version,
+                    format!("magnet:?xt=urn:btih:{}-{}", name, version),
+                    1_000,
+                )
+                .with_dependencies(dependencies.iter().map(|dep| dep.to_string()).collect()),
+            )?;
+        }
+
+        Ok(db)
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn resolved_names(entries: &[PackageEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name().as_str()).collect()
+    }
+
+    #[test]
+    fn resolve_multiple_packages() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = seeded_db(&temp_dir, "packages.db", &[("first", &[]), ("second", &[])])?;
+        let installed_db = seeded_db(&temp_dir, "installed.db", &[])?;
+
+        let (entries, failures) = resolve_packages(
+            &package_db,
+            &installed_db,
+            &names(&["first", "missing", "second"]),
+        );
+        assert_eq!(resolved_names(&entries), ["first", "second"]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "missing");
+
+        Ok(())
+    }
+
+    #[test]
+    fn version_constraints() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = seeded_versioned_db(
+            &temp_dir,
+            "packages.db",
+            &[
+                ("ripgrep", Version::new(1, 0, 0), &[]),
+                ("ripgrep", Version::new(1, 2, 5), &[]),
+                ("ripgrep", Version::new(2, 0, 0), &[]),
+            ],
+        )?;
+        let installed_db = seeded_db(&temp_dir, "installed.db", &[])?;
+        let resolve_version = |spec: &str| {
+            let (entries, failures) = resolve_packages(&package_db, &installed_db, &names(&[spec]));
+            match (entries.first(), failures.first()) {
+                (Some(entry), None) => Ok(*entry.version()),
+                (None, Some((_, err))) => Err(err.to_string()),
+                _ => panic!("Expected exactly one entry or failure"),
+            }
+        };
+
+        assert_eq!(resolve_version("ripgrep"), Ok(Version::new(2, 0, 0)));
+        assert_eq!(resolve_version("ripgrep@1.2.5"), Ok(Version::new(1, 2, 5)));
+        assert_eq!(resolve_version("ripgrep>=1.1.0"), Ok(Version::new(2, 0, 0)));
+        assert_eq!(resolve_version("ripgrep<2.0.0"), Ok(Version::new(1, 2, 5)));
+        assert_eq!(
+            resolve_version("ripgrep@1.1.0"),
+            Err("No version of ripgrep satisfies =1.1.0".to_owned())
+        );
+        assert!(resolve_version("ripgrep>=").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn upgrades() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = seeded_versioned_db(
+            &temp_dir,
+            "packages.db",
+            &[
+                ("ripgrep", Version::new(1, 0, 0), &[]),
+                ("ripgrep", Version::new(1, 1, 0), &[]),
+                ("fd", Version::new(2, 0, 0), &[]),
+            ],
+        )?;
+        let installed_db = seeded_versioned_db(
+            &temp_dir,
+            "installed.db",
+            &[
+                ("ripgrep", Version::new(1, 0, 0), &[]),
+                ("fd", Version::new(2, 0, 0), &[]),
+            ],
+        )?;
+
+        let (upgrades, failures) = find_upgrades(&package_db, &installed_db, &[])?;
+        assert!(failures.is_empty());
+        assert_eq!(resolved_names(&upgrades), ["ripgrep"]);
+        assert_eq!(upgrades[0].version(), &Version::new(1, 1, 0));
+
+        let (_, failures) = find_upgrades(&package_db, &installed_db, &names(&["missing"]))?;
+        assert_eq!(failures.len(), 1);
+
+        // Upgrading replaces the installed row
+        for upgrade in upgrades {
+            installed_db.update_package_entry(upgrade)?;
+        }
+        let installed = installed_db.query("ripgrep")?.ok_or("Missing ripgrep")?;
+        assert_eq!(installed.version(), &Version::new(1, 1, 0));
+        assert_eq!(installed.torrent_name(), "ripgrep-1.1.0");
+        assert_eq!(installed_db.list_all()?.len(), 2);
+        let (upgrades, _) = find_upgrades(&package_db, &installed_db, &[])?;
+        assert!(upgrades.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn outdated_packages() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = seeded_versioned_db(
+            &temp_dir,
+            "packages.db",
+            &[
+                ("ripgrep", Version::new(1, 1, 0), &[]),
+                ("fd", Version::new(2, 0, 0), &[]),
+            ],
+        )?;
+        let installed_db = seeded_versioned_db(
+            &temp_dir,
+            "installed.db",
+            &[
+                ("ripgrep", Version::new(1, 0, 0), &[]),
+                ("fd", Version::new(2, 0, 0), &[]),
+                ("unlisted", Version::new(1, 0, 0), &[]),
+            ],
+        )?;
+
+        let (outdated, failures) = find_outdated(&package_db, &installed_db, &[])?;
+        assert!(failures.is_empty());
+        let versions: Vec<_> = outdated
+            .iter()
+            .map(|outdated| {
+                (
+                    outdated.installed.name().as_str(),
+                    *outdated.installed.version(),
+                    *outdated.available.version(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            versions,
+            [("ripgrep", Version::new(1, 0, 0), Version::new(1, 1, 0))]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependency_chain() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = seeded_db(
+            &temp_dir,
+            "packages.db",
+            &[("a", &["b"]), ("b", &["c"]), ("c", &[]), ("d", &["c"])],
+        )?;
+        let installed_db = seeded_db(&temp_dir, "installed.db", &[])?;
+
+        let (entries, failures) = resolve_packages(&package_db, &installed_db, &names(&["a", "d"]));
+        assert!(failures.is_empty());
+        assert_eq!(resolved_names(&entries), ["c", "b", "a", "d"]);
+
+        // Installed dependencies get skipped
+        let installed_db = seeded_db(&temp_dir, "installed_c.db", &[("c", &[])])?;
+        let (entries, failures) = resolve_packages(&package_db, &installed_db, &names(&["a"]));
+        assert!(failures.is_empty());
+        assert_eq!(resolved_names(&entries), ["b", "a"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependency_cycle() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_db = seeded_db(
+            &temp_dir,
+            "packages.db",
+            &[("a", &["b"]), ("b", &["a"]), ("c", &[])],
+        )?;
+        let installed_db = seeded_db(&temp_dir, "installed.db", &[])?;
+
+        let (entries, failures) = resolve_packages(&package_db, &installed_db, &names(&["a", "c"]));
+        assert_eq!(resolved_names(&entries), ["c"]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "a");
+        assert!(failures[0].1.to_string().contains("a -> b -> a"));
+
+        Ok(())
+    }
+}
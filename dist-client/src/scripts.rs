@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Runs the package's `scripts/uninstall.sh` if it has one
+pub fn run_uninstall(package_dir: &Path) -> Result<()> {
+    run_script(&package_dir.join("scripts").join("uninstall.sh"))
+}
+
+fn run_script(script: &Path) -> Result<()> {
+    if !script.is_file() {
+        return Ok(());
+    }
+
+    // The permissions don't survive being sent as a torrent
+    fs::set_permissions(script, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed making {} executable", script.display()))?;
+
+    let status = Command::new(script)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("Failed running {}", script.display()))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", script.display(), status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn uninstall_script() -> BoxResult<()> {
+        let package_dir = tempfile::tempdir()?;
+        let package_dir = package_dir.path();
+
+        // Packages don't have to have an uninstall script
+        run_uninstall(package_dir)?;
+
+        let scripts_dir = package_dir.join("scripts");
+        let marker = package_dir.join("uninstalled");
+        fs::create_dir(&scripts_dir)?;
+        fs::write(
+            scripts_dir.join("uninstall.sh"),
+            format!("#!/bin/sh\ntouch '{}'\n", marker.display()),
+        )?;
+        run_uninstall(package_dir)?;
+        assert!(marker.exists());
+
+        fs::write(scripts_dir.join("uninstall.sh"), "#!/bin/sh\nexit 1\n")?;
+        assert!(run_uninstall(package_dir).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use dist_package_db::models::PackageEntry;
+use transmission_wrapper::{
+    bytes::Bytes,
+    entry::{Entry, Status},
+    error::Error,
+};
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::download::Torrents;
+
+// Each torrent finishes after a set amount of refreshes
+pub(crate) struct FakeTorrents {
+    schedule: HashMap<String, (String, usize)>,
+    entries: Vec<(Entry, usize)>,
+    pub events: Vec<String>,
+    // Sets the flag on the nth refresh, like a `SIGINT` coming in
+    pub interrupt: Option<(usize, Arc<AtomicBool>)>,
+    // Has the refresh that sets the flag fail too, like the signal killing `transmission-remote`
+    pub kills_remote: bool,
+}
+
+impl FakeTorrents {
+    pub fn new(packages: &[(&PackageEntry, usize)]) -> Self {
+        let schedule = packages
+            .iter()
+            .map(|(package, refreshes)| {
+                (
+                    package.magnet().to_owned(),
+                    (package.torrent_name().to_owned(), *refreshes),
+                )
+            })
+            .collect();
+
+        Self {
+            schedule,
+            entries: Vec::new(),
+            events: Vec::new(),
+            interrupt: None,
+            kills_remote: false,
+        }
+    }
+
+    // Acts like the torrent was added by an earlier run that got interrupted
+    pub fn with_existing(mut self, package: &PackageEntry, refreshes: usize) -> Self {
+        let id = self.entries.len() as u64 + 1;
+        let entry = Entry::new(
+            id,
+            Some(Bytes(100)),
+            Bytes(10),
+            Status::Downloading,
+            package.torrent_name().to_owned(),
+        );
+        self.entries.push((entry, refreshes));
+        self
+    }
+}
+
+impl Torrents for FakeTorrents {
+    fn download_torrent(&mut self, magnet: &str) -> Result<(), Error> {
+        let (name, refreshes) = self
+            .schedule
+            .get(magnet)
+            .cloned()
+            .ok_or_else(|| Error::InvalidMagnet(magnet.to_owned()))?;
+        self.events.push(format!("added {}", name));
+        let id = self.entries.len() as u64 + 1;
+        let entry = Entry::new(id, None, Bytes::zero(), Status::Downloading, name);
+        self.entries.push((entry, refreshes));
+        Ok(())
+    }
+
+    fn refresh(&mut self) -> Result<(), Error> {
+        if let Some((remaining, flag)) = &mut self.interrupt {
+            *remaining -= 1;
+            if *remaining == 0 {
+                flag.store(true, Ordering::SeqCst);
+                if self.kills_remote {
+                    return Err(Error::RemoteCommandFailed("Interrupted".to_owned()));
+                }
+            }
+        }
+
+        for (entry, remaining) in &mut self.entries {
+            if *remaining == 0 {
+                continue;
+            }
+
+            *remaining -= 1;
+            if *remaining == 0 {
+                entry.update(Some(Bytes(100)), Bytes(100), Status::Seeding);
+                self.events.push(format!("finished {}", entry.name()));
+            } else {
+                entry.update(
+                    Some(Bytes(100)),
+                    Bytes(100 / (*remaining as u64 + 1)),
+                    Status::Downloading,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_by_name(&self, name: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .map(|(entry, _)| entry)
+            .find(|entry| entry.name() == name)
+    }
+
+    fn remove_torrent(&mut self, id: u64) -> Result<(), Error> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(entry, _)| *entry.id() == id)
+            .ok_or(Error::MissingTorrent(id))?;
+        let (entry, _) = self.entries.remove(index);
+        self.events.push(format!("removed {}", entry.name()));
+        Ok(())
+    }
+
+    fn stop_torrent(&mut self, id: u64) -> Result<(), Error> {
+        let name = self.name_of(id)?;
+        self.events.push(format!("stopped {}", name));
+        Ok(())
+    }
+
+    fn set_seed_ratio(&mut self, id: u64, ratio: f32) -> Result<(), Error> {
+        let name = self.name_of(id)?;
+        self.events.push(format!("seed ratio {} {}", ratio, name));
+        Ok(())
+    }
+}
+
+impl FakeTorrents {
+    fn name_of(&self, id: u64) -> Result<String, Error> {
+        self.entries
+            .iter()
+            .map(|(entry, _)| entry)
+            .find(|entry| *entry.id() == id)
+            .map(|entry| entry.name().to_owned())
+            .ok_or(Error::MissingTorrent(id))
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use dist_package::checksum;
+use dist_package_db::models::PackageEntry;
+
+use std::{io, path::Path};
+
+/// How a package's downloaded data compares to its listed checksum
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrity {
+    Intact,
+    /// Either the data changed or it's gone entirely
+    Corrupt,
+    /// Older listings don't include a checksum to compare against
+    Unverified,
+}
+
+pub fn check(entry: &PackageEntry, data_dir: &Path) -> Result<Integrity> {
+    let expected = match entry.sha256() {
+        Some(expected) => expected,
+        None => return Ok(Integrity::Unverified),
+    };
+
+    match checksum::sha256(&data_dir.join(entry.torrent_name())) {
+        Ok(actual) if &actual == expected => Ok(Integrity::Intact),
+        Ok(_) => Ok(Integrity::Corrupt),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Integrity::Corrupt),
+        Err(err) => Err(err)
+            .with_context(|| format!("Failed hashing the downloaded data for {}", entry.name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dist_package::manifest::Version;
+
+    use std::fs;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn corrupt_package() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let data_dir = temp_dir.path();
+        let package_dir = data_dir.join("ripgrep-1.0.0");
+        fs::create_dir_all(package_dir.join("assets"))?;
+        fs::write(package_dir.join("assets").join("rg"), "binary")?;
+        let entry = PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(1, 0, 0),
+            "magnet:?xt=urn:btih:ripgrep".to_owned(),
+            100,
+        );
+        let sha256 = checksum::sha256(&package_dir)?;
+
+        assert_eq!(check(&entry, data_dir)?, Integrity::Unverified);
+        let entry = entry.with_sha256(sha256);
+        assert_eq!(check(&entry, data_dir)?, Integrity::Intact);
+
+        fs::write(package_dir.join("assets").join("rg"), "tampered")?;
+        assert_eq!(check(&entry, data_dir)?, Integrity::Corrupt);
+
+        fs::remove_dir_all(&package_dir)?;
+        assert_eq!(check(&entry, data_dir)?, Integrity::Corrupt);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+-- Checksum of the package's contents so that clients can verify what they downloaded
+ALTER TABLE packages ADD COLUMN sha256 VARCHAR
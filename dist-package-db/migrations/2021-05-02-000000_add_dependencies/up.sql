@@ -0,0 +1,2 @@
+-- Names of the packages that need to be installed along with this one as a JSON array
+ALTER TABLE packages ADD COLUMN dependencies VARCHAR NOT NULL DEFAULT '[]'
@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+-- SQLite can't drop columns, so rebuild the table without it instead
+CREATE TABLE packages_old (
+    torrent_name VARCHAR UNIQUE NOT NULL,
+    name VARCHAR NOT NULL,
+    version INTEGER NOT NULL,
+    magnet VARCHAR NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    sha256 VARCHAR,
+    dependencies VARCHAR NOT NULL DEFAULT '[]',
+    PRIMARY KEY (name, version)
+);
+INSERT INTO packages_old
+    SELECT torrent_name, name, version, magnet, size_bytes, sha256, dependencies FROM packages;
+DROP TABLE packages;
+ALTER TABLE packages_old RENAME TO packages
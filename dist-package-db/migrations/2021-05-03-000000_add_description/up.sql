@@ -0,0 +1,2 @@
+-- A short human readable summary of the package
+ALTER TABLE packages ADD COLUMN description VARCHAR
@@ -3,10 +3,16 @@ mod schema;
 
 use diesel::prelude::*;
 use diesel::result::QueryResult;
+use diesel::sql_types::Text;
 use diesel::sqlite::SqliteConnection;
-use dist_package::AddedPackage;
+use dist_package::{spec::VersionReq, AddedPackage};
 
-use std::{fs, path::Path};
+use std::{
+    convert::TryFrom,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
 
 use crate::{
     database::{models::DbPackageEntry, schema::packages},
@@ -18,16 +24,28 @@ embed_migrations!("./migrations");
 
 pub type RowID = usize;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum MissingDBAction {
     Create,
+    /// Like `Create`, but a corrupt database also gets replaced with a fresh one
+    Recreate,
+    #[default]
     RaiseError,
 }
 
-impl Default for MissingDBAction {
-    fn default() -> Self {
-        Self::RaiseError
-    }
+/// What listed packages get ordered by. Ties are always broken by name and then version
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortBy {
+    #[default]
+    Name,
+    Version,
+    Size,
+}
+
+#[derive(QueryableByName)]
+struct IntegrityCheck {
+    #[sql_type = "Text"]
+    integrity_check: String,
 }
 
 pub struct DistpacDB {
@@ -39,23 +57,30 @@ impl DistpacDB {
         let database_url = db_path.to_str().ok_or(DatabaseError::InvalidDatabaseUrl)?;
 
         let connection = if db_path.exists() {
-            SqliteConnection::establish(&database_url)?
+            let connection = SqliteConnection::establish(database_url)?;
+            if is_intact(&connection) {
+                connection
+            } else if let MissingDBAction::Recreate = action {
+                drop(connection);
+                fs::remove_file(db_path)?;
+                SqliteConnection::establish(database_url)?
+            } else {
+                return Err(DatabaseError::Corrupt(db_path.to_owned()));
+            }
         } else {
             // Diesel will create a new SQLite DB when connecting, so need to decide what to do if
             // it's missing
             match action {
-                MissingDBAction::Create => {
+                MissingDBAction::Create | MissingDBAction::Recreate => {
                     // Create any needed directories
                     fs::create_dir_all(
-                        &db_path
+                        db_path
                             .parent()
                             .expect("Package database path must have a parent"),
                     )?;
 
-                    // And create the database
-                    let connection = SqliteConnection::establish(&database_url)?;
-                    embedded_migrations::run(&connection)?;
-                    connection
+                    // And create the database. The schema gets set up by migrating below
+                    SqliteConnection::establish(database_url)?
                 }
                 MissingDBAction::RaiseError => {
                     return Err(DatabaseError::MissingDatabase);
@@ -63,7 +88,27 @@ impl DistpacDB {
             }
         };
 
-        Ok(Self { connection })
+        let db = Self { connection };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    /// Applies any migrations that the database is missing, in order, so that databases made with
+    /// an older schema get upgraded in place
+    ///
+    /// Diesel keeps track of the applied versions in its `__diesel_schema_migrations` table, so this
+    /// is a no-op for an up to date database
+    pub fn migrate(&self) -> Result<(), DatabaseError> {
+        embedded_migrations::run(&self.connection)?;
+        Ok(())
+    }
+
+    /// Checks that the database can be read with the expected schema
+    pub fn validate(&self) -> QueryResult<()> {
+        packages::table
+            .limit(1)
+            .load::<DbPackageEntry>(&self.connection)
+            .map(|_| ())
     }
 
     pub fn add_package(&self, package: AddedPackage) -> QueryResult<RowID> {
@@ -72,13 +117,58 @@ impl DistpacDB {
             .execute(&self.connection)
     }
 
+    /// Adds the entry, replacing any existing row for the same name and version so that adding a
+    /// package again is idempotent
     // TODO: Would be nice to combine this with `.add_package()`
     pub fn add_package_entry(&self, package: PackageEntry) -> QueryResult<RowID> {
-        diesel::insert_into(packages::table)
+        diesel::replace_into(packages::table)
             .values(&DbPackageEntry::from(package))
             .execute(&self.connection)
     }
 
+    /// Adds all of the entries at once, or none of them if any fail. Like
+    /// [`add_package_entry`](Self::add_package_entry) existing rows get replaced
+    pub fn add_package_entries(&self, packages: Vec<PackageEntry>) -> QueryResult<RowID> {
+        let db_packages: Vec<_> = packages.into_iter().map(DbPackageEntry::from).collect();
+        self.connection.transaction(|| {
+            diesel::replace_into(packages::table)
+                .values(&db_packages)
+                .execute(&self.connection)
+        })
+    }
+
+    // Like `add_package_entries`, but existing rows fail the whole batch instead
+    fn insert_package_entries(&self, packages: Vec<PackageEntry>) -> QueryResult<RowID> {
+        let db_packages: Vec<_> = packages.into_iter().map(DbPackageEntry::from).collect();
+        self.connection.transaction(|| {
+            diesel::insert_into(packages::table)
+                .values(&db_packages)
+                .execute(&self.connection)
+        })
+    }
+
+    /// Replaces every version of the package with the new entry, e.g. when it gets upgraded
+    ///
+    /// Errors with [`NotFound`](diesel::result::Error::NotFound) when there's no row to replace,
+    /// leaving the database untouched
+    pub fn update_package_entry(&self, package: PackageEntry) -> QueryResult<RowID> {
+        let db_package = DbPackageEntry::from(package);
+        self.connection.transaction(|| {
+            // Updating the version in place would collide on the primary key when the name has
+            // several versions, so swap the rows out instead
+            let removed =
+                diesel::delete(packages::table.filter(packages::name.eq(&db_package.name)))
+                    .execute(&self.connection)?;
+            if removed == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+
+            diesel::insert_into(packages::table)
+                .values(&db_package)
+                .execute(&self.connection)
+        })
+    }
+
     // TODO: this seems specific for no reason. Would be nice to generalize
     pub fn remove_by_name(&self, name: &str) -> QueryResult<RowID> {
         diesel::delete(packages::table.filter(packages::name.eq(name))).execute(&self.connection)
@@ -90,15 +180,681 @@ impl DistpacDB {
         Ok(packages)
     }
 
-    // TODO: this could return multiple packages with different versions. Really should sort by the
-    // version number and return the first result.
+    /// Returns up to `limit` packages after skipping the first `offset`, ordered by `sort` or the
+    /// reverse of it
+    pub fn list_page(
+        &self,
+        sort: SortBy,
+        reverse: bool,
+        limit: usize,
+        offset: usize,
+    ) -> QueryResult<Vec<PackageEntry>> {
+        let query = packages::table.into_boxed();
+        let query = match (sort, reverse) {
+            (SortBy::Name, false) => query.order((packages::name.asc(), packages::version.asc())),
+            (SortBy::Name, true) => query.order((packages::name.desc(), packages::version.desc())),
+            (SortBy::Version, false) => {
+                query.order((packages::version.asc(), packages::name.asc()))
+            }
+            (SortBy::Version, true) => {
+                query.order((packages::version.desc(), packages::name.desc()))
+            }
+            (SortBy::Size, false) => query.order((
+                packages::size_bytes.asc(),
+                packages::name.asc(),
+                packages::version.asc(),
+            )),
+            (SortBy::Size, true) => query.order((
+                packages::size_bytes.desc(),
+                packages::name.desc(),
+                packages::version.desc(),
+            )),
+        };
+
+        let db_packages: Vec<DbPackageEntry> = query
+            .limit(i64::try_from(limit).unwrap_or(i64::MAX))
+            .offset(i64::try_from(offset).unwrap_or(i64::MAX))
+            .load(&self.connection)?;
+        let packages = db_packages.into_iter().map(PackageEntry::from).collect();
+        Ok(packages)
+    }
+
+    /// The total number of package entries, counting each version separately
+    pub fn count(&self) -> QueryResult<usize> {
+        let count: i64 = packages::table.count().get_result(&self.connection)?;
+        Ok(usize::try_from(count).unwrap_or_default())
+    }
+
+    /// Writes out every package as newline-delimited JSON
+    pub fn export(&self, mut writer: impl Write) -> Result<(), DatabaseError> {
+        for package in self.list_all()? {
+            serde_json::to_writer(&mut writer, &package)?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds all of the packages from an [`export`](Self::export), or none of them if any fail,
+    /// returning how many were added
+    pub fn import(&self, reader: impl Read) -> Result<usize, DatabaseError> {
+        let mut packages = Vec::new();
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                packages.push(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok(self.insert_package_entries(packages)?)
+    }
+
+    /// Finds the packages where every one of the terms shows up in either the name or description,
+    /// ignoring case
+    ///
+    /// Packages that match on the name alone come before the ones that needed the description
+    pub fn search(&self, terms: &[String]) -> QueryResult<Vec<PackageEntry>> {
+        let mut query = packages::table.order(packages::name).into_boxed();
+        for term in terms {
+            let pattern = format!("%{}%", escape_like(term));
+            query = query.filter(
+                packages::name
+                    .like(pattern.clone())
+                    .escape('\\')
+                    .or(packages::description.like(pattern).escape('\\')),
+            );
+        }
+
+        let db_packages: Vec<DbPackageEntry> = query.load(&self.connection)?;
+        let mut packages: Vec<_> = db_packages.into_iter().map(PackageEntry::from).collect();
+        // Stable, so each group stays sorted by name
+        packages.sort_by_key(|package| {
+            let name = package.name().to_lowercase();
+            !terms.iter().all(|term| name.contains(&term.to_lowercase()))
+        });
+        Ok(packages)
+    }
+
+    /// Returns up to `limit` packages whose names start with `prefix`, ignoring case, ordered by
+    /// name and then version
+    pub fn list_by_prefix(&self, prefix: &str, limit: usize) -> QueryResult<Vec<PackageEntry>> {
+        let db_packages: Vec<DbPackageEntry> = packages::table
+            .filter(
+                packages::name
+                    .like(format!("{}%", escape_like(prefix)))
+                    .escape('\\'),
+            )
+            .order((packages::name, packages::version))
+            .limit(i64::try_from(limit).unwrap_or(i64::MAX))
+            .load(&self.connection)?;
+        let packages = db_packages.into_iter().map(PackageEntry::from).collect();
+        Ok(packages)
+    }
+
+    /// Returns the latest version of the package
     pub fn query(&self, name: &str) -> QueryResult<Option<PackageEntry>> {
+        self.query_version(name, VersionReq::Any)
+    }
+
+    /// Returns the latest version of the package that satisfies `req`
+    pub fn query_version(&self, name: &str, req: VersionReq) -> QueryResult<Option<PackageEntry>> {
         let db_packages: Vec<DbPackageEntry> = packages::table
             .filter(packages::name.eq(name))
             .load(&self.connection)?;
-        let mut packages: Vec<_> = db_packages.into_iter().map(PackageEntry::from).collect();
-        let maybe_package = packages.drain(..).next();
+        let maybe_package = db_packages
+            .into_iter()
+            .map(PackageEntry::from)
+            .filter(|package| req.matches(*package.version()))
+            .max_by_key(|package| *package.version());
 
         Ok(maybe_package)
     }
 }
+
+// A file that isn't a database at all fails the check outright instead of reporting any problems
+fn is_intact(connection: &SqliteConnection) -> bool {
+    diesel::sql_query("PRAGMA integrity_check")
+        .load::<IntegrityCheck>(connection)
+        .map(|results| results.iter().all(|result| result.integrity_check == "ok"))
+        .unwrap_or(false)
+}
+
+// Matches the text literally instead of letting it act as a `LIKE` pattern
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use diesel::connection::SimpleConnection;
+    use dist_package::manifest::Version;
+    use tempfile::TempDir;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    fn seeded_db(names: &[&str]) -> BoxResult<(TempDir, DistpacDB)> {
+        let versioned: Vec<_> = names
+            .iter()
+            .map(|name| (*name, Version::new(1, 0, 0)))
+            .collect();
+        seeded_versioned_db(&versioned)
+    }
+
+    fn seeded_described_db(packages: &[(&str, &str)]) -> BoxResult<(TempDir, DistpacDB)> {
+        let (temp_dir, db) = seeded_db(&[])?;
+        for (name, description) in packages {
+            db.add_package_entry(
+                PackageEntry::new(
+                    name.to_string(),
+                    Version::new(1, 0, 0),
+                    format!("magnet:?xt=urn:btih:{}", name),
+                    1_000,
+                )
+                .with_description(description.to_string()),
+            )?;
+        }
+
+        Ok((temp_dir, db))
+    }
+
+    fn seeded_versioned_db(packages: &[(&str, Version)]) -> BoxResult<(TempDir, DistpacDB)> {
+        let temp_dir = tempfile::tempdir()?;
+        let db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        for (name, version) in packages {
+            db.add_package_entry(PackageEntry::new(
+                name.to_string(),
+                *version,
+                format!("magnet:?xt=urn:btih:{}-{}", name, version),
+                1_000,
+            ))?;
+        }
+
+        Ok((temp_dir, db))
+    }
+
+    fn names(packages: &[PackageEntry]) -> Vec<&str> {
+        packages
+            .iter()
+            .map(|package| package.name().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn query_versions() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_versioned_db(&[
+            ("ripgrep", Version::new(1, 0, 0)),
+            ("ripgrep", Version::new(1, 2, 5)),
+            ("ripgrep", Version::new(2, 0, 0)),
+        ])?;
+        let version = |req| -> BoxResult<Option<Version>> {
+            Ok(db
+                .query_version("ripgrep", req)?
+                .map(|package| *package.version()))
+        };
+
+        // The latest matching version gets picked
+        assert_eq!(version(VersionReq::Any)?, Some(Version::new(2, 0, 0)));
+        assert_eq!(
+            db.query("ripgrep")?.map(|package| *package.version()),
+            Some(Version::new(2, 0, 0))
+        );
+        assert_eq!(
+            version(VersionReq::Exact(Version::new(1, 2, 5)))?,
+            Some(Version::new(1, 2, 5))
+        );
+        assert_eq!(
+            version(VersionReq::GreaterEq(Version::new(1, 2, 0)))?,
+            Some(Version::new(2, 0, 0))
+        );
+        assert_eq!(
+            version(VersionReq::Less(Version::new(2, 0, 0)))?,
+            Some(Version::new(1, 2, 5))
+        );
+        assert_eq!(version(VersionReq::Exact(Version::new(1, 1, 0)))?, None);
+        assert_eq!(version(VersionReq::Greater(Version::new(2, 0, 0)))?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_old_schema() -> BoxResult<()> {
+        // Set up a database with only the initial migration applied
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("packages.db");
+        let connection = SqliteConnection::establish(db_path.to_str().ok_or("Invalid path")?)?;
+        diesel_migrations::setup_database(&connection)?;
+        connection.batch_execute(include_str!(
+            "../../migrations/2021-04-02-221323_init/up.sql"
+        ))?;
+        connection.batch_execute(
+            "INSERT INTO __diesel_schema_migrations (version) VALUES ('20210402221323');
+            INSERT INTO packages VALUES ('ripgrep-1.0.0', 'ripgrep', 65536, 'magnet', 1000);",
+        )?;
+        drop(connection);
+
+        // Which gets upgraded instead of being treated as missing
+        let db = DistpacDB::connect(&db_path, MissingDBAction::RaiseError)?;
+        let old = db.query("ripgrep")?.ok_or("Missing old entry")?;
+        assert_eq!(old.sha256(), &None);
+        assert!(old.dependencies().is_empty());
+        db.add_package_entry(
+            PackageEntry::new(
+                "fd".to_owned(),
+                Version::new(8, 2, 1),
+                "magnet:?xt=urn:btih:fd".to_owned(),
+                1_000,
+            )
+            .with_sha256("abc123".to_owned())
+            .with_dependencies(vec!["ripgrep".to_owned()]),
+        )?;
+        drop(db);
+
+        // And reconnecting leaves the upgraded database alone
+        let db = DistpacDB::connect(&db_path, MissingDBAction::RaiseError)?;
+        let new = db.query("fd")?.ok_or("Missing new entry")?;
+        assert_eq!(new.sha256().as_deref(), Some("abc123"));
+        assert_eq!(new.dependencies(), &["ripgrep"]);
+        assert_eq!(db.list_all()?.len(), 2);
+
+        // While a missing database is still an error
+        assert!(matches!(
+            DistpacDB::connect(
+                &temp_dir.path().join("missing.db"),
+                MissingDBAction::RaiseError
+            ),
+            Err(DatabaseError::MissingDatabase)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_insert() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_db(&["ripgrep"])?;
+        let entry = |name: &str| {
+            PackageEntry::new(
+                name.to_owned(),
+                Version::new(1, 0, 0),
+                format!("magnet:?xt=urn:btih:{}", name),
+                1_000,
+            )
+        };
+
+        assert_eq!(db.add_package_entries(vec![entry("fd"), entry("sd")])?, 2);
+        assert_eq!(names(&db.list_all()?), ["ripgrep", "fd", "sd"]);
+
+        // The duplicate replaces the existing row instead of failing the batch
+        let rebuilt = entry("ripgrep").with_description("Rebuilt".to_owned());
+        db.add_package_entries(vec![entry("bat"), rebuilt, entry("exa")])?;
+        assert_eq!(
+            names(&db.list_all()?),
+            ["fd", "sd", "bat", "ripgrep", "exa"]
+        );
+        let ripgrep = db.query("ripgrep")?.ok_or("Missing ripgrep")?;
+        assert_eq!(ripgrep.description().as_deref(), Some("Rebuilt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_import() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_versioned_db(&[
+            ("ripgrep", Version::new(12, 1, 1)),
+            ("ripgrep", Version::new(13, 0, 0)),
+        ])?;
+        db.add_package_entry(
+            PackageEntry::new(
+                "fd".to_owned(),
+                Version::new(8, 2, 1),
+                "magnet:?xt=urn:btih:fd".to_owned(),
+                2_000,
+            )
+            .with_sha256("ab".repeat(32))
+            .with_dependencies(vec!["ripgrep".to_owned()])
+            .with_description("A simple and fast alternative to find".to_owned()),
+        )?;
+        let mut exported = Vec::new();
+        db.export(&mut exported)?;
+        assert_eq!(String::from_utf8(exported.clone())?.lines().count(), 3);
+
+        let (_fresh_dir, fresh_db) = seeded_db(&[])?;
+        assert_eq!(fresh_db.import(exported.as_slice())?, 3);
+        let mut reexported = Vec::new();
+        fresh_db.export(&mut reexported)?;
+        assert_eq!(reexported, exported);
+
+        // Importing the same entries again is all or nothing
+        assert!(fresh_db.import(exported.as_slice()).is_err());
+        assert_eq!(fresh_db.list_all()?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_db() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("packages.db");
+        let db = DistpacDB::connect(&db_path, MissingDBAction::Create)?;
+        db.add_package_entry(PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(1, 0, 0),
+            "magnet:?xt=urn:btih:ripgrep".to_owned(),
+            1_000,
+        ))?;
+        drop(db);
+
+        // Cut off partway through
+        let contents = fs::read(&db_path)?;
+        fs::write(&db_path, &contents[..contents.len() / 2])?;
+        assert!(matches!(
+            DistpacDB::connect(&db_path, MissingDBAction::RaiseError),
+            Err(DatabaseError::Corrupt(_))
+        ));
+        assert!(matches!(
+            DistpacDB::connect(&db_path, MissingDBAction::Create),
+            Err(DatabaseError::Corrupt(_))
+        ));
+
+        // Recreating starts over with an empty database
+        let db = DistpacDB::connect(&db_path, MissingDBAction::Recreate)?;
+        assert!(db.list_all()?.is_empty());
+
+        fs::write(&db_path, "not a database")?;
+        assert!(matches!(
+            DistpacDB::connect(&db_path, MissingDBAction::RaiseError),
+            Err(DatabaseError::Corrupt(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn readd_entry() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_db(&["ripgrep"])?;
+        db.add_package_entry(
+            PackageEntry::new(
+                "ripgrep".to_owned(),
+                Version::new(1, 0, 0),
+                "magnet:?xt=urn:btih:new".to_owned(),
+                2_000,
+            )
+            .with_description("Rebuilt".to_owned()),
+        )?;
+
+        let packages = db.list_all()?;
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].magnet(), "magnet:?xt=urn:btih:new");
+        assert_eq!(*packages[0].size(), 2_000);
+        assert_eq!(packages[0].description().as_deref(), Some("Rebuilt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_entry() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_db(&["ripgrep", "fd"])?;
+        let upgraded = PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(1, 1, 0),
+            "magnet:?xt=urn:btih:ripgrep-1.1.0".to_owned(),
+            2_000,
+        )
+        .with_sha256("abc123".to_owned());
+
+        assert_eq!(db.update_package_entry(upgraded)?, 1);
+        let entry = db.query("ripgrep")?.ok_or("Missing ripgrep")?;
+        assert_eq!(entry.version(), &Version::new(1, 1, 0));
+        assert_eq!(entry.torrent_name(), "ripgrep-1.1.0");
+        assert_eq!(entry.magnet(), "magnet:?xt=urn:btih:ripgrep-1.1.0");
+        assert_eq!(entry.size(), &2_000);
+        assert_eq!(entry.sha256().as_deref(), Some("abc123"));
+        // Other rows are left alone
+        assert_eq!(
+            db.query("fd")?.map(|entry| *entry.version()),
+            Some(Version::new(1, 0, 0))
+        );
+
+        let missing = PackageEntry::new(
+            "missing".to_owned(),
+            Version::new(1, 0, 0),
+            "magnet:?xt=urn:btih:missing".to_owned(),
+            1_000,
+        );
+        assert!(matches!(
+            db.update_package_entry(missing),
+            Err(diesel::result::Error::NotFound)
+        ));
+        assert_eq!(db.list_all()?.len(), 2);
+
+        // Several versions of the same name all get replaced
+        db.add_package_entry(PackageEntry::new(
+            "fd".to_owned(),
+            Version::new(2, 0, 0),
+            "magnet:?xt=urn:btih:fd-2.0.0".to_owned(),
+            1_000,
+        ))?;
+        db.update_package_entry(PackageEntry::new(
+            "fd".to_owned(),
+            Version::new(3, 0, 0),
+            "magnet:?xt=urn:btih:fd-3.0.0".to_owned(),
+            1_000,
+        ))?;
+        let fd_versions: Vec<_> = db
+            .list_all()?
+            .into_iter()
+            .filter(|entry| entry.name() == "fd")
+            .map(|entry| *entry.version())
+            .collect();
+        assert_eq!(fd_versions, [Version::new(3, 0, 0)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stored_torrent_name() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_db(&[])?;
+        db.add_package_entry(
+            PackageEntry::builder()
+                .name("ripgrep".to_owned())
+                .version(Version::new(12, 1, 1))
+                .magnet("magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567".to_owned())
+                .size(1_000)
+                .torrent_name("ripgrep".to_owned())
+                .build()?,
+        )?;
+
+        let entry = db.query("ripgrep")?.ok_or("Missing ripgrep")?;
+        assert_eq!(entry.torrent_name(), "ripgrep");
+
+        Ok(())
+    }
+
+    #[test]
+    fn descriptions() -> BoxResult<()> {
+        let (_temp_dir, db) =
+            seeded_described_db(&[("ripgrep", "Recursively search directories")])?;
+        db.add_package_entry(PackageEntry::new(
+            "fd".to_owned(),
+            Version::new(1, 0, 0),
+            "magnet:?xt=urn:btih:fd".to_owned(),
+            1_000,
+        ))?;
+
+        let described = db.query("ripgrep")?.ok_or("Missing ripgrep")?;
+        assert_eq!(
+            described.description().as_deref(),
+            Some("Recursively search directories")
+        );
+        let undescribed = db.query("fd")?.ok_or("Missing fd")?;
+        assert_eq!(undescribed.description(), &None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn search() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_db(&["rust-analyzer", "rust-std", "ripgrep", "100%_done"])?;
+
+        let terms =
+            |terms: &[&str]| -> Vec<String> { terms.iter().map(|t| t.to_string()).collect() };
+        assert_eq!(
+            names(&db.search(&terms(&["rust"]))?),
+            ["rust-analyzer", "rust-std"]
+        );
+        assert_eq!(
+            names(&db.search(&terms(&["RUST", "ANAL"]))?),
+            ["rust-analyzer"]
+        );
+        assert!(db.search(&terms(&["rust", "grep"]))?.is_empty());
+
+        // Wildcards only match literally
+        assert_eq!(names(&db.search(&terms(&["%_"]))?), ["100%_done"]);
+        assert!(db.search(&terms(&["r_st"]))?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_listing() -> BoxResult<()> {
+        let mut packages = Vec::new();
+        for i in 0..50 {
+            packages.push((format!("lib-{:02}", i), Version::new(1, 0, 0)));
+            packages.push((format!("tool-{:02}", i), Version::new(1, 0, 0)));
+        }
+        packages.push(("lib-00".to_owned(), Version::new(0, 9, 0)));
+        packages.push(("lib_x".to_owned(), Version::new(1, 0, 0)));
+        let packages: Vec<_> = packages
+            .iter()
+            .map(|(name, version)| (name.as_str(), *version))
+            .collect();
+        let (_temp_dir, db) = seeded_versioned_db(&packages)?;
+
+        let listed = db.list_by_prefix("LIB-", 3)?;
+        assert_eq!(names(&listed), ["lib-00", "lib-00", "lib-01"]);
+        assert_eq!(*listed[0].version(), Version::new(0, 9, 0));
+        assert_eq!(db.list_by_prefix("lib-", 100)?.len(), 51);
+        assert_eq!(db.list_by_prefix("tool", 100)?.len(), 50);
+
+        // Wildcards only match literally
+        assert_eq!(names(&db.list_by_prefix("lib_", 100)?), ["lib_x"]);
+        assert!(db.list_by_prefix("%", 100)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn paged_listing() -> BoxResult<()> {
+        let names_owned: Vec<_> = (0..50).map(|i| format!("pkg-{:02}", i)).collect();
+        let seeded: Vec<_> = names_owned.iter().map(String::as_str).collect();
+        let (_temp_dir, db) = seeded_db(&seeded)?;
+        assert_eq!(db.count()?, 50);
+
+        assert_eq!(
+            names(&db.list_page(SortBy::Name, false, 3, 0)?),
+            ["pkg-00", "pkg-01", "pkg-02"]
+        );
+        assert_eq!(
+            names(&db.list_page(SortBy::Name, false, 10, 20)?),
+            &seeded[20..30],
+            "The window should start at the offset"
+        );
+        // Running off the end just gives back whatever is left
+        assert_eq!(
+            names(&db.list_page(SortBy::Name, false, 10, 45)?),
+            &seeded[45..]
+        );
+        assert!(db.list_page(SortBy::Name, false, 10, 50)?.is_empty());
+        assert_eq!(db.list_page(SortBy::Name, false, usize::MAX, 0)?.len(), 50);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_listing() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_db(&[])?;
+        for (name, version, size) in [
+            ("huge", Version::new(1, 1, 0), 3_500_000_000),
+            ("big", Version::new(1, 0, 0), 2_000_000_000),
+            ("medium", Version::new(0, 10, 0), 500_000_000),
+            ("small", Version::new(0, 9, 0), 1_000),
+        ] {
+            db.add_package_entry(PackageEntry::new(
+                name.to_owned(),
+                version,
+                format!("magnet:?xt=urn:btih:{}", name),
+                size,
+            ))?;
+        }
+
+        let sorted = |sort, reverse| -> BoxResult<Vec<String>> {
+            Ok(db
+                .list_page(sort, reverse, usize::MAX, 0)?
+                .iter()
+                .map(|package| package.name().to_owned())
+                .collect())
+        };
+        assert_eq!(
+            sorted(SortBy::Name, false)?,
+            ["big", "huge", "medium", "small"]
+        );
+        assert_eq!(
+            sorted(SortBy::Name, true)?,
+            ["small", "medium", "huge", "big"]
+        );
+        // Numerically, so 0.10.0 is newer than 0.9.0 and 2 GB is bigger than 500 MB. Sizes past
+        // `i32::MAX` don't wrap around either
+        assert_eq!(
+            sorted(SortBy::Version, false)?,
+            ["small", "medium", "big", "huge"]
+        );
+        assert_eq!(
+            sorted(SortBy::Size, false)?,
+            ["small", "medium", "big", "huge"]
+        );
+        assert_eq!(
+            sorted(SortBy::Size, true)?,
+            ["huge", "big", "medium", "small"]
+        );
+        let huge = db.query("huge")?.ok_or("Missing huge")?;
+        assert_eq!(huge.size(), &3_500_000_000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_descriptions() -> BoxResult<()> {
+        let (_temp_dir, db) = seeded_described_db(&[
+            ("ripgrep", "Recursively search directories for a regex"),
+            ("fd", "A simple and fast alternative to find"),
+            ("grep-tools", "Extra tools"),
+            ("sd", "Intuitive find and replace"),
+            ("search-index", "An index"),
+        ])?;
+
+        let terms =
+            |terms: &[&str]| -> Vec<String> { terms.iter().map(|t| t.to_string()).collect() };
+        // Name matches come first
+        assert_eq!(
+            names(&db.search(&terms(&["search"]))?),
+            ["search-index", "ripgrep"]
+        );
+        assert_eq!(
+            names(&db.search(&terms(&["grep"]))?),
+            ["grep-tools", "ripgrep"]
+        );
+        assert_eq!(names(&db.search(&terms(&["FIND"]))?), ["fd", "sd"]);
+        // Every term has to match somewhere, but not all in the same place
+        assert_eq!(names(&db.search(&terms(&["find", "fast"]))?), ["fd"]);
+        assert_eq!(names(&db.search(&terms(&["rip", "regex"]))?), ["ripgrep"]);
+        assert!(db.search(&terms(&["find", "regex"]))?.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use dist_package::{AddedPackage, Torrent};
 
+use std::convert::TryFrom;
+
 use crate::{database::schema::packages, models::PackageEntry};
 
 #[derive(Insertable, Queryable, Debug)]
@@ -9,25 +11,11 @@ pub(crate) struct DbPackageEntry {
     pub(crate) name: String,
     pub(crate) version: i32,
     pub(crate) magnet: String,
-    pub(crate) size_bytes: i32,
-}
-
-impl DbPackageEntry {
-    pub fn new(
-        torrent_name: String,
-        name: String,
-        version: i32,
-        magnet: String,
-        size_bytes: i32,
-    ) -> Self {
-        Self {
-            torrent_name,
-            name,
-            version,
-            magnet,
-            size_bytes,
-        }
-    }
+    pub(crate) size_bytes: i64,
+    pub(crate) sha256: Option<String>,
+    // Stored as a JSON array of package names
+    pub(crate) dependencies: String,
+    pub(crate) description: Option<String>,
 }
 
 impl From<PackageEntry> for DbPackageEntry {
@@ -38,9 +26,23 @@ impl From<PackageEntry> for DbPackageEntry {
             version,
             magnet,
             size,
+            sha256,
+            dependencies,
+            description,
         } = package_entry;
 
-        Self::new(torrent_name, name, version.as_i32(), magnet, size as i32)
+        Self {
+            torrent_name,
+            name,
+            version: version.as_i32(),
+            magnet,
+            // SQLite integers are signed 64-bit, which is still way past any real package
+            size_bytes: i64::try_from(size).unwrap_or(i64::MAX),
+            sha256,
+            dependencies: serde_json::to_string(&dependencies)
+                .expect("A list of strings is always valid JSON"),
+            description,
+        }
     }
 }
 
@@ -49,6 +51,7 @@ impl From<AddedPackage> for DbPackageEntry {
         let AddedPackage {
             name,
             version,
+            description,
             torrent:
                 Torrent {
                     name: torrent_name,
@@ -59,6 +62,9 @@ impl From<AddedPackage> for DbPackageEntry {
             ..
         } = package;
 
-        Self::new(torrent_name, name, version.as_i32(), magnet, size as i32)
+        let mut package_entry = PackageEntry::new(name, version, magnet, size);
+        package_entry.torrent_name = torrent_name;
+        package_entry.description = description;
+        Self::from(package_entry)
     }
 }
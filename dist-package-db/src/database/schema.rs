@@ -4,6 +4,9 @@ table! {
         name -> Text,
         version -> Integer,
         magnet -> Text,
-        size_bytes -> Integer,
+        size_bytes -> BigInt,
+        sha256 -> Nullable<Text>,
+        dependencies -> Text,
+        description -> Nullable<Text>,
     }
 }
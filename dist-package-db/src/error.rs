@@ -1,8 +1,8 @@
-use diesel::result::ConnectionError;
+use diesel::result::{ConnectionError, Error as QueryError};
 use diesel_migrations::RunMigrationsError;
 use thiserror::Error;
 
-use std::io;
+use std::{io, path::PathBuf};
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
@@ -14,6 +14,22 @@ pub enum DatabaseError {
     Migration(#[from] RunMigrationsError),
     #[error("The database is missing and is configured to not be created automatically")]
     MissingDatabase,
+    #[error("The database at {0} is corrupt")]
+    Corrupt(PathBuf),
     #[error("Encountered IO error when dealing with database Error: {0}")]
     Io(#[from] io::Error),
+    #[error("Failed querying the database Error: {0}")]
+    Query(#[from] QueryError),
+    #[error("Package entry isn't correctly formatted Error: {0}")]
+    InvalidEntry(#[from] serde_json::Error),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum EntryError {
+    #[error("Package entry is missing its {0}")]
+    MissingField(&'static str),
+    #[error("Package entry has an empty {0}")]
+    EmptyField(&'static str),
+    #[error("Magnet link {0} doesn't have a valid info-hash")]
+    InvalidMagnet(String),
 }
@@ -1,3 +1,6 @@
+// The diesel 1.x macros generate impls inside of functions
+#![allow(non_local_definitions)]
+
 #[macro_use]
 extern crate diesel;
 #[macro_use]
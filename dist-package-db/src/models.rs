@@ -1,9 +1,12 @@
 use dist_package::manifest::Version;
 use getset::Getters;
+use serde::{Deserialize, Serialize};
 
-use crate::database::models::DbPackageEntry;
+use std::{cmp::Ordering, convert::TryFrom};
 
-#[derive(Getters, Debug)]
+use crate::{database::models::DbPackageEntry, error::EntryError};
+
+#[derive(Getters, Serialize, Deserialize, Clone, Debug)]
 #[getset(get = "pub")]
 pub struct PackageEntry {
     pub(crate) torrent_name: String,
@@ -11,28 +14,325 @@ pub struct PackageEntry {
     pub(crate) version: Version,
     pub(crate) magnet: String,
     pub(crate) size: u64,
+    /// Hex encoded SHA-256 of the package's contents. Older listings don't include it
+    pub(crate) sha256: Option<String>,
+    /// Names of the packages that need to be installed along with this one
+    pub(crate) dependencies: Vec<String>,
+    /// A short human readable summary of the package
+    pub(crate) description: Option<String>,
 }
 
 impl PackageEntry {
-    fn new(name: String, version: Version, magnet: String, size: u64) -> Self {
+    pub fn new(name: String, version: Version, magnet: String, size: u64) -> Self {
         Self {
             torrent_name: format!("{}-{}", name, version),
             name,
             version,
             magnet,
             size,
+            sha256: None,
+            dependencies: Vec::new(),
+            description: None,
         }
     }
+
+    pub fn with_sha256(mut self, sha256: String) -> Self {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    pub fn with_dependencies(mut self, dependencies: Vec<String>) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    pub fn builder() -> PackageEntryBuilder {
+        PackageEntryBuilder::default()
+    }
+
+    /// The info-hash from the magnet link, if it has a valid one
+    pub fn info_hash(&self) -> Option<&str> {
+        info_hash(&self.magnet)
+    }
+}
+
+/// Builds a [`PackageEntry`] while checking that everything it needs is set and valid
+#[derive(Default, Debug)]
+pub struct PackageEntryBuilder {
+    name: Option<String>,
+    version: Option<Version>,
+    magnet: Option<String>,
+    size: Option<u64>,
+    torrent_name: Option<String>,
+    sha256: Option<String>,
+    dependencies: Vec<String>,
+    description: Option<String>,
+}
+
+impl PackageEntryBuilder {
+    pub fn name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn magnet(mut self, magnet: String) -> Self {
+        self.magnet = Some(magnet);
+        self
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Defaults to `<name>-<version>` like [`PackageEntry::new`]
+    pub fn torrent_name(mut self, torrent_name: String) -> Self {
+        self.torrent_name = Some(torrent_name);
+        self
+    }
+
+    pub fn sha256(mut self, sha256: String) -> Self {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    pub fn dependencies(mut self, dependencies: Vec<String>) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    pub fn description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    pub fn build(self) -> Result<PackageEntry, EntryError> {
+        let name = non_empty("name", self.name)?;
+        let version = self.version.ok_or(EntryError::MissingField("version"))?;
+        let magnet = non_empty("magnet", self.magnet)?;
+        if info_hash(&magnet).is_none() {
+            return Err(EntryError::InvalidMagnet(magnet));
+        }
+        let size = self.size.ok_or(EntryError::MissingField("size"))?;
+        let torrent_name = match self.torrent_name {
+            Some(torrent_name) => non_empty("torrent_name", Some(torrent_name))?,
+            None => format!("{}-{}", name, version),
+        };
+
+        Ok(PackageEntry {
+            torrent_name,
+            name,
+            version,
+            magnet,
+            size,
+            sha256: self.sha256,
+            dependencies: self.dependencies,
+            description: self.description,
+        })
+    }
+}
+
+fn non_empty(field: &'static str, value: Option<String>) -> Result<String, EntryError> {
+    match value {
+        Some(value) if value.trim().is_empty() => Err(EntryError::EmptyField(field)),
+        Some(value) => Ok(value),
+        None => Err(EntryError::MissingField(field)),
+    }
+}
+
+// The `xt=urn:btih:<hash>` parameter of the magnet link where the hash is either 40 hex or 32
+// base32 characters
+fn info_hash(magnet: &str) -> Option<&str> {
+    let params = magnet.strip_prefix("magnet:?")?;
+    params
+        .split('&')
+        .filter_map(|param| param.strip_prefix("xt=urn:btih:"))
+        .find(|hash| match hash.len() {
+            40 => hash.chars().all(|c| c.is_ascii_hexdigit()),
+            32 => hash
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || ('2'..='7').contains(&c)),
+            _ => false,
+        })
+}
+
+// Entries are identified by their name and version, which is also the database's primary key
+impl PartialEq for PackageEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PackageEntry {}
+
+impl PartialOrd for PackageEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PackageEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name
+            .cmp(&other.name)
+            .then_with(|| self.version.cmp(&other.version))
+    }
 }
 
 impl From<DbPackageEntry> for PackageEntry {
     fn from(db_package: DbPackageEntry) -> Self {
         let version = Version::from(db_package.version);
-        Self::new(
+        let mut package = Self::new(
             db_package.name,
             version,
             db_package.magnet,
-            db_package.size_bytes as u64,
+            u64::try_from(db_package.size_bytes).unwrap_or_default(),
+        );
+        package.torrent_name = db_package.torrent_name;
+        package.sha256 = db_package.sha256;
+        package.description = db_package.description;
+        // The column defaults to an empty list, so anything else unreadable is treated the same
+        package.dependencies = serde_json::from_str(&db_package.dependencies).unwrap_or_default();
+        package
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, version: &str) -> PackageEntry {
+        PackageEntry::new(
+            name.to_owned(),
+            version.parse().unwrap(),
+            format!("magnet:?xt=urn:btih:{}-{}", name, version),
+            1_000,
         )
     }
+
+    #[test]
+    fn entry_ord() {
+        assert!(entry("ripgrep", "1.10.0") > entry("ripgrep", "1.9.0"));
+        assert!(entry("fd", "9.0.0") < entry("ripgrep", "1.0.0"));
+        assert_eq!(
+            entry("ripgrep", "1.0.0"),
+            entry("ripgrep", "1.0.0").with_sha256("abc123".to_owned())
+        );
+
+        let mut entries = [
+            entry("ripgrep", "1.10.0"),
+            entry("fd", "8.2.1"),
+            entry("ripgrep", "1.9.0"),
+        ];
+        entries.sort();
+        let sorted: Vec<_> = entries.iter().map(PackageEntry::torrent_name).collect();
+        assert_eq!(sorted, ["fd-8.2.1", "ripgrep-1.9.0", "ripgrep-1.10.0"]);
+    }
+
+    const MAGNET: &str = "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567&dn=ripgrep";
+
+    fn full_builder() -> PackageEntryBuilder {
+        PackageEntry::builder()
+            .name("ripgrep".to_owned())
+            .version(Version::new(12, 1, 1))
+            .magnet(MAGNET.to_owned())
+            .size(1_000)
+    }
+
+    #[test]
+    fn built_entry() -> Result<(), EntryError> {
+        let entry = full_builder()
+            .sha256("abc123".to_owned())
+            .dependencies(vec!["pcre2".to_owned()])
+            .description("Fast grep".to_owned())
+            .build()?;
+        assert_eq!(entry.torrent_name(), "ripgrep-12.1.1");
+        assert_eq!(entry.size(), &1_000);
+        assert_eq!(entry.sha256().as_deref(), Some("abc123"));
+        assert_eq!(entry.dependencies(), &["pcre2"]);
+        assert_eq!(entry.description().as_deref(), Some("Fast grep"));
+        assert_eq!(
+            entry.info_hash(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+
+        let entry = full_builder()
+            .magnet("magnet:?xt=urn:btih:YNCKHTQCWBJHV77S6XAJRCNCUAXRDSEC".to_owned())
+            .torrent_name("ripgrep".to_owned())
+            .build()?;
+        assert_eq!(entry.torrent_name(), "ripgrep");
+
+        // Base32 hashes aren't case sensitive
+        full_builder()
+            .magnet("magnet:?xt=urn:btih:ynckhtqcwbjhv77s6xajrcncuaxrdsec".to_owned())
+            .build()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_errors() {
+        let missing = |builder: PackageEntryBuilder| builder.build().unwrap_err();
+        let base = || PackageEntry::builder();
+        assert_eq!(
+            missing(base().version(Version::new(1, 0, 0))),
+            EntryError::MissingField("name")
+        );
+        assert_eq!(
+            missing(base().name("ripgrep".to_owned()).magnet(MAGNET.to_owned())),
+            EntryError::MissingField("version")
+        );
+        assert_eq!(
+            missing(
+                base()
+                    .name("ripgrep".to_owned())
+                    .version(Version::new(1, 0, 0))
+            ),
+            EntryError::MissingField("magnet")
+        );
+        assert_eq!(
+            missing(
+                base()
+                    .name("ripgrep".to_owned())
+                    .version(Version::new(1, 0, 0))
+                    .magnet(MAGNET.to_owned())
+            ),
+            EntryError::MissingField("size")
+        );
+
+        assert_eq!(
+            missing(full_builder().name(" ".to_owned())),
+            EntryError::EmptyField("name")
+        );
+        assert_eq!(
+            missing(full_builder().magnet(String::new())),
+            EntryError::EmptyField("magnet")
+        );
+        assert_eq!(
+            missing(full_builder().torrent_name(String::new())),
+            EntryError::EmptyField("torrent_name")
+        );
+
+        for magnet in &[
+            "magnet:?xt=urn:btih:ripgrep",
+            "magnet:?dn=ripgrep",
+            "http://0123456789abcdef0123456789abcdef01234567",
+            "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef0123456z",
+        ] {
+            assert_eq!(
+                missing(full_builder().magnet(magnet.to_string())),
+                EntryError::InvalidMagnet(magnet.to_string())
+            );
+        }
+    }
 }
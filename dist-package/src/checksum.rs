@@ -0,0 +1,73 @@
+use sha2::{Digest, Sha256};
+
+use std::{fs, io, path::Path};
+
+/// Hashes the contents of a package as a hex encoded SHA-256
+///
+/// Works on both single files and directories. Directories are walked in sorted order with each
+/// file's relative path hashed along with its contents, so the hash changes if any file gets
+/// modified, renamed, added, or removed
+pub fn sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    hash_path(&mut hasher, path, Path::new(""))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn hash_path(hasher: &mut Sha256, path: &Path, relative: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        let mut children = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+
+        for child in children {
+            hash_path(hasher, &path.join(&child), &relative.join(&child))?;
+        }
+    } else {
+        // Null bytes can't show up in paths so they keep the path and contents from blending
+        // together
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(path)?);
+        hasher.update([0]);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn tampering() -> TestResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let package_dir = temp_dir.path().join("package");
+        fs::create_dir_all(package_dir.join("scripts"))?;
+        fs::write(
+            package_dir.join("manifest.yaml"),
+            "name: package\nversion: 1.0.0\n",
+        )?;
+        fs::write(package_dir.join("scripts").join("install.sh"), "echo hi\n")?;
+
+        let original = sha256(&package_dir)?;
+        assert_eq!(original.len(), 64);
+        assert_eq!(sha256(&package_dir)?, original);
+
+        fs::write(package_dir.join("scripts").join("install.sh"), "echo bye\n")?;
+        assert_ne!(sha256(&package_dir)?, original);
+
+        // Moving a file around counts as a change too
+        fs::write(package_dir.join("scripts").join("install.sh"), "echo hi\n")?;
+        assert_eq!(sha256(&package_dir)?, original);
+        fs::rename(
+            package_dir.join("scripts").join("install.sh"),
+            package_dir.join("install.sh"),
+        )?;
+        assert_ne!(sha256(&package_dir)?, original);
+
+        Ok(())
+    }
+}
@@ -24,4 +24,28 @@ pub enum ParseVersionError {
     InvalidValuesCount(usize),
     #[error("Expected valid u16, but got {0} instead")]
     InvalidValue(String),
+    #[error("Pre-release and build tags aren't supported, but got {0}")]
+    UnsupportedTag(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseSpecError {
+    #[error("Package spec {0} is missing a package name")]
+    MissingName(String),
+    #[error("Package spec has an invalid version Error: {0}")]
+    InvalidVersion(#[from] ParseVersionError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SignatureError {
+    #[error("Public key isn't valid base64 Error: {0}")]
+    InvalidKey(base64::DecodeError),
+    #[error("Signature isn't valid base64 Error: {0}")]
+    InvalidSignatureEncoding(base64::DecodeError),
+    #[error("Signature doesn't match the signed content")]
+    Mismatch,
+    #[error("Private key isn't valid base64 Error: {0}")]
+    InvalidPrivateKeyEncoding(base64::DecodeError),
+    #[error("Private key isn't a valid ed25519 PKCS#8 key")]
+    InvalidPrivateKey,
 }
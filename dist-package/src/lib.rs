@@ -12,24 +12,32 @@ use crate::{
 };
 
 pub use imdl_wrapper::Torrent;
+pub mod checksum;
 pub mod error;
 pub mod manifest;
+pub mod signature;
+pub mod spec;
 mod utils;
 
 pub struct NewPackage {
     name: String,
     version: Version,
+    description: Option<String>,
     package_path: PathBuf,
 }
 
 impl NewPackage {
     pub fn new(package_path: PathBuf) -> Result<Self, PackageError> {
         Self::validate(&package_path)?;
-        let Manifest { name, version } =
-            Manifest::try_from(package_path.join("manifest.yaml").as_path())?;
+        let Manifest {
+            name,
+            version,
+            description,
+        } = Manifest::try_from(package_path.join("manifest.yaml").as_path())?;
         Ok(Self {
             name,
             version,
+            description,
             package_path,
         })
     }
@@ -58,6 +66,7 @@ impl NewPackage {
 pub struct AddedPackage {
     pub name: String,
     pub version: Version,
+    pub description: Option<String>,
     pub installed_path: PathBuf,
     pub torrent: Torrent,
 }
@@ -72,15 +81,26 @@ impl AddedPackage {
         let NewPackage {
             name,
             version,
+            description,
             package_path: old_package_path,
         } = new_package;
 
         // Rename the package directory based on the package name and version
         let package_dir_name = format!("{}-{}", name, version);
         let mut package_path = old_package_path.clone();
-        package_path.set_file_name(package_dir_name);
+        package_path.set_file_name(&package_dir_name);
         fs::rename(&old_package_path, &package_path)?;
 
+        // Adding the same package again replaces what was there from last time
+        let package_dir = packages_dir.join(&package_dir_name);
+        if package_dir.exists() {
+            fs::remove_dir_all(&package_dir)?;
+        }
+        let torrent_path = torrent_dir.join(format!("{}.torrent", package_dir_name));
+        if torrent_path.exists() {
+            fs::remove_file(&torrent_path)?;
+        }
+
         // And move the package to the installed location
         fs_extra::move_items(
             &[&package_path],
@@ -89,12 +109,12 @@ impl AddedPackage {
         )?;
 
         // Create the torrent
-        let package_dir = packages_dir.join(&package_path.file_name().unwrap());
         let torrent = Torrent::create(&package_dir, &torrent_dir, announce_url)?;
 
         Ok(Self {
             name,
             version,
+            description,
             installed_path: package_dir,
             torrent,
         })
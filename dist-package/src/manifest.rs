@@ -1,4 +1,4 @@
-use serde::{de::Error, Deserialize, Deserializer};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
 use std::{cmp::Ordering, convert::TryFrom, fmt, fs::File, path::Path, str::FromStr};
 
@@ -8,6 +8,8 @@ use crate::error::{PackageError, ParseVersionError};
 pub struct Manifest {
     pub name: String,
     pub version: Version,
+    /// A short human readable summary of the package
+    pub description: Option<String>,
 }
 
 impl TryFrom<&Path> for Manifest {
@@ -95,6 +97,11 @@ impl FromStr for Version {
     type Err = ParseVersionError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Versions get packed into a single integer in the database, so there's no room for tags
+        if s.contains(['-', '+']) {
+            return Err(Self::Err::UnsupportedTag(s.to_string()));
+        }
+
         let parts: Vec<_> = s.split('.').collect();
 
         // There should be three values
@@ -126,6 +133,15 @@ impl<'de> Deserialize<'de> for Version {
     }
 }
 
+impl Serialize for Version {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,6 +172,28 @@ mod tests {
         assert!(one_two_three >= one_two_two);
         assert!(one_two_three == one_two_three);
         assert!(one_two_three < two_zero_zero);
+
+        // Each part compares numerically instead of as text
+        let mut versions: Vec<Version> = ["1.10.0", "1.9.0", "0.1.10", "1.9.10", "0.1.9", "10.0.0"]
+            .iter()
+            .map(|version| version.parse().unwrap())
+            .collect();
+        versions.sort();
+        let sorted: Vec<_> = versions.iter().map(Version::to_string).collect();
+        assert_eq!(
+            sorted,
+            ["0.1.9", "0.1.10", "1.9.0", "1.9.10", "1.10.0", "10.0.0"]
+        );
+    }
+
+    #[test]
+    fn version_tags() {
+        for tagged in ["1.0.0-alpha", "1.0.0-rc.1", "1.0.0+build.5"].iter() {
+            assert!(matches!(
+                tagged.parse::<Version>(),
+                Err(ParseVersionError::UnsupportedTag(_))
+            ));
+        }
     }
 
     #[test]
@@ -0,0 +1,117 @@
+use ring::signature::{Ed25519KeyPair, UnparsedPublicKey, ED25519};
+
+use crate::error::SignatureError;
+
+/// Checks a detached ed25519 signature over `content`
+///
+/// Both the public key and the signature are expected to be base64 encoded, which is how they show
+/// up in the client's config and the `packages.db.sig` file respectively
+pub fn verify(public_key: &str, content: &[u8], signature: &str) -> Result<(), SignatureError> {
+    let public_key = base64::decode(public_key.trim()).map_err(SignatureError::InvalidKey)?;
+    let signature =
+        base64::decode(signature.trim()).map_err(SignatureError::InvalidSignatureEncoding)?;
+
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(content, &signature)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+/// Makes a detached ed25519 signature over `content`
+///
+/// The private key is expected to be a base64 encoded PKCS#8 document and the signature is base64
+/// encoded to match what [`verify`] takes
+pub fn sign(private_key: &str, content: &[u8]) -> Result<String, SignatureError> {
+    let private_key =
+        base64::decode(private_key.trim()).map_err(SignatureError::InvalidPrivateKeyEncoding)?;
+    let key_pair =
+        Ed25519KeyPair::from_pkcs8(&private_key).map_err(|_| SignatureError::InvalidPrivateKey)?;
+
+    Ok(base64::encode(key_pair.sign(content)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ring::{rand::SystemRandom, signature::KeyPair};
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    fn key_pair() -> BoxResult<Ed25519KeyPair> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| "Failed generating key")?;
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|_| "Invalid key".into())
+    }
+
+    #[test]
+    fn valid_signature() -> BoxResult<()> {
+        let key_pair = key_pair()?;
+        let public_key = base64::encode(key_pair.public_key());
+        let content = b"package database";
+        let signature = base64::encode(key_pair.sign(content));
+
+        verify(&public_key, content, &signature)?;
+        // Trailing newlines from the signature file are fine
+        verify(&public_key, content, &format!("{}\n", signature))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_content() -> BoxResult<()> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| "Failed generating key")?;
+        let private_key = base64::encode(pkcs8.as_ref());
+        let public_key = base64::encode(
+            Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                .map_err(|_| "Invalid key")?
+                .public_key(),
+        );
+
+        let signature = sign(&format!("{}\n", private_key), b"package database")?;
+        verify(&public_key, b"package database", &signature)?;
+
+        assert!(matches!(
+            sign("not base64!", b"package database"),
+            Err(SignatureError::InvalidPrivateKeyEncoding(_))
+        ));
+        assert!(matches!(
+            sign(&public_key, b"package database"),
+            Err(SignatureError::InvalidPrivateKey)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_signature() -> BoxResult<()> {
+        let key_pair = key_pair()?;
+        let public_key = base64::encode(key_pair.public_key());
+        let signature = base64::encode(key_pair.sign(b"package database"));
+
+        assert!(matches!(
+            verify(&public_key, b"tampered database", &signature),
+            Err(SignatureError::Mismatch)
+        ));
+
+        let mut tampered = base64::decode(&signature)?;
+        tampered[0] ^= 0xFF;
+        assert!(matches!(
+            verify(&public_key, b"package database", &base64::encode(tampered)),
+            Err(SignatureError::Mismatch)
+        ));
+
+        // A different key
+        let other_key = base64::encode(self::key_pair()?.public_key());
+        assert!(matches!(
+            verify(&other_key, b"package database", &signature),
+            Err(SignatureError::Mismatch)
+        ));
+        assert!(matches!(
+            verify("not base64!", b"package database", &signature),
+            Err(SignatureError::InvalidKey(_))
+        ));
+
+        Ok(())
+    }
+}
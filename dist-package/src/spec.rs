@@ -0,0 +1,148 @@
+use std::{fmt, str::FromStr};
+
+use crate::{error::ParseSpecError, manifest::Version};
+
+/// Which versions of a package are acceptable
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VersionReq {
+    #[default]
+    Any,
+    Exact(Version),
+    Greater(Version),
+    GreaterEq(Version),
+    Less(Version),
+    LessEq(Version),
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: Version) -> bool {
+        match *self {
+            Self::Any => true,
+            Self::Exact(req) => version == req,
+            Self::Greater(req) => version > req,
+            Self::GreaterEq(req) => version >= req,
+            Self::Less(req) => version < req,
+            Self::LessEq(req) => version <= req,
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "*"),
+            Self::Exact(version) => write!(f, "={}", version),
+            Self::Greater(version) => write!(f, ">{}", version),
+            Self::GreaterEq(version) => write!(f, ">={}", version),
+            Self::Less(version) => write!(f, "<{}", version),
+            Self::LessEq(version) => write!(f, "<={}", version),
+        }
+    }
+}
+
+/// A package name along with the versions that are acceptable e.g. `name`, `name@1.2.3`, or
+/// `name>=1.2.0`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageSpec {
+    pub name: String,
+    pub req: VersionReq,
+}
+
+impl PackageSpec {
+    pub fn any(name: String) -> Self {
+        Self {
+            name,
+            req: VersionReq::Any,
+        }
+    }
+}
+
+impl FromStr for PackageSpec {
+    type Err = ParseSpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Longer operators go first so that `>=` doesn't get read as `>`
+        type ToReq = fn(Version) -> VersionReq;
+        const OPERATORS: [(&str, ToReq); 6] = [
+            (">=", VersionReq::GreaterEq),
+            ("<=", VersionReq::LessEq),
+            ("@", VersionReq::Exact),
+            ("=", VersionReq::Exact),
+            (">", VersionReq::Greater),
+            ("<", VersionReq::Less),
+        ];
+
+        let (name, req) = match s.find(['@', '=', '>', '<']) {
+            Some(index) => {
+                let (name, constraint) = s.split_at(index);
+                let (operator, to_req) = OPERATORS
+                    .iter()
+                    .find(|(operator, _)| constraint.starts_with(operator))
+                    .expect("The constraint starts with one of the operators");
+                let version = constraint[operator.len()..].trim().parse()?;
+                (name, to_req(version))
+            }
+            None => (s, VersionReq::Any),
+        };
+
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Self::Err::MissingName(s.to_owned()));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            req,
+        })
+    }
+}
+
+impl fmt::Display for PackageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.req {
+            VersionReq::Any => write!(f, "{}", self.name),
+            req => write!(f, "{}{}", self.name, req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn parse_specs() -> TestResult<()> {
+        let spec: PackageSpec = "ripgrep".parse()?;
+        assert_eq!(spec, PackageSpec::any("ripgrep".to_owned()));
+
+        let spec: PackageSpec = "ripgrep@12.1.1".parse()?;
+        assert_eq!(spec.name, "ripgrep");
+        assert_eq!(spec.req, VersionReq::Exact(Version::new(12, 1, 1)));
+
+        let spec: PackageSpec = "ripgrep>=1.2.0".parse()?;
+        assert_eq!(spec.req, VersionReq::GreaterEq(Version::new(1, 2, 0)));
+
+        let spec: PackageSpec = "ripgrep<2.0.0".parse()?;
+        assert_eq!(spec.req, VersionReq::Less(Version::new(2, 0, 0)));
+
+        assert!("@1.2.3".parse::<PackageSpec>().is_err());
+        assert!("ripgrep>=1.2".parse::<PackageSpec>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn matching() {
+        let version = Version::new(1, 2, 3);
+
+        assert!(VersionReq::Any.matches(version));
+        assert!(VersionReq::Exact(version).matches(version));
+        assert!(!VersionReq::Exact(Version::new(1, 2, 4)).matches(version));
+        assert!(VersionReq::GreaterEq(Version::new(1, 2, 0)).matches(version));
+        assert!(!VersionReq::Greater(version).matches(version));
+        assert!(VersionReq::LessEq(version).matches(version));
+        assert!(!VersionReq::Less(version).matches(version));
+    }
+}
@@ -19,11 +19,15 @@ pub struct Opts {
 #[derive(Clap, Debug)]
 pub enum SubCommand {
     /// Start all (or just some) of the server components.
-    Start(ComponentListing),
+    Start(StartComponents),
     /// Stop all (or just some) of the server components.
     Stop(ComponentListing),
+    /// Show whether all (or just some) of the server components are running.
+    Status(ComponentListing),
     /// Adds a new package to be served by the distpac.
     Add(AddPackage),
+    /// Removes packages from being served by the distpac.
+    Remove(RemovePackage),
 }
 
 #[derive(Clap, Debug, PartialEq)]
@@ -31,16 +35,38 @@ pub struct ComponentListing {
     /// Ignore the seeder component.
     #[clap(long)]
     pub no_seeder: bool,
-    /// Ignore the database component.
-    #[clap(long)]
-    pub no_database: bool,
+    /// Ignore the HTTP server that serves the package database.
+    #[clap(long, alias = "no-database")]
+    pub no_http: bool,
     /// Ignore the tracker component.
     #[clap(long)]
     pub no_tracker: bool,
 }
 
+#[derive(Clap, Debug)]
+pub struct StartComponents {
+    /// Run the components in the background and track them with PID files.
+    #[clap(long)]
+    pub daemon: bool,
+    #[clap(flatten)]
+    pub components: ComponentListing,
+}
+
 #[derive(Clap, Debug)]
 pub struct AddPackage {
     /// Paths to all the packages to add.
     pub package_paths: Vec<PathBuf>,
+    /// Print the magnet link and name of each added package, one per line.
+    #[clap(long)]
+    pub print_magnets: bool,
+}
+
+#[derive(Clap, Debug)]
+pub struct RemovePackage {
+    /// Names of all the packages to remove.
+    #[clap(required = true)]
+    pub names: Vec<String>,
+    /// Also delete the torrent files and package data.
+    #[clap(long)]
+    pub purge: bool,
 }
@@ -1,22 +1,35 @@
 use anyhow::Result;
-use log::info;
+use dist_utils::misc::{Stopped, DEFAULT_GRACE_PERIOD};
+use log::{info, warn};
+use sysinfo::{Pid, ProcessExt, System, SystemExt};
 use transmission_wrapper::{Transmission, TransmissionOpts};
 
-use std::process::Command;
+use std::{
+    fmt, fs,
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+};
 
-use crate::cli::ComponentListing;
+use crate::{cli::ComponentListing, config::ServerConfig, pid_file};
 
-const DATABASE_SERVER_NAME: &str = "named-file-server";
+const HTTP_SERVER_NAME: &str = "named-file-server";
 const TRACKER_SERVER_NAME: &str = "opentracker";
+const SEEDER_NAME: &str = "transmission-daemon";
 
 pub struct ComponentManager {
     components: Vec<Box<dyn Component>>,
 }
 
 impl ComponentManager {
-    pub fn start(&self) -> Result<()> {
+    /// Starts the components, detaching them and writing their PID files when `daemon` is set
+    pub fn start(&self, daemon: bool) -> Result<()> {
         for component in &self.components {
-            component.start()?;
+            if daemon {
+                component.start(Some(&dist_utils::path::pid_file(component.name())))?;
+            } else {
+                component.start(None)?;
+            }
         }
 
         Ok(())
@@ -24,23 +37,145 @@ impl ComponentManager {
 
     pub fn stop(&self) {
         for component in &self.components {
-            component.stop();
+            let pid_file = dist_utils::path::pid_file(component.name());
+            match pid_file::read(&pid_file) {
+                Ok(Some(pid)) => {
+                    info!("Shutting down {} (pid {})", component.name(), pid);
+                    match stop_pid(pid, component.process_name()) {
+                        Stopped::NotRunning => {
+                            warn!("The {} was no longer running", component.name())
+                        }
+                        Stopped::Gracefully => {}
+                        Stopped::Forcefully => warn!(
+                            "The {} didn't shut down within {:?} and was killed",
+                            component.name(),
+                            DEFAULT_GRACE_PERIOD
+                        ),
+                    }
+                    if let Err(err) = fs::remove_file(&pid_file) {
+                        warn!("Failed removing {} Error: {}", pid_file.display(), err);
+                    }
+                }
+                // Without `--daemon` there's nothing better to go off of than the process name
+                Ok(None) => component.stop(),
+                Err(err) => warn!("Failed stopping the {} Error: {}", component.name(), err),
+            }
         }
     }
+
+    pub fn status(&self, processes: &dyn ProcessTable) -> Vec<(&'static str, ComponentStatus)> {
+        self.components
+            .iter()
+            .map(|component| (component.name(), component.status(processes)))
+            .collect()
+    }
+}
+
+/// Looks up the ids of running processes by name
+pub trait ProcessTable {
+    fn pids(&self, name: &str) -> Vec<Pid>;
+}
+
+pub struct SystemProcesses(System);
+
+impl SystemProcesses {
+    pub fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_processes();
+        Self(system)
+    }
+}
+
+impl ProcessTable for SystemProcesses {
+    fn pids(&self, name: &str) -> Vec<Pid> {
+        self.0
+            .get_process_by_name(short_name(name))
+            .iter()
+            .map(|process| process.pid())
+            .collect()
+    }
+}
+
+// Process names are truncated the same as in `stop_process_by_name`
+fn short_name(name: &str) -> &str {
+    if name.len() < 15 {
+        name
+    } else {
+        &name[..15]
+    }
+}
+
+// The process name gets checked so that a reused pid from a stale PID file doesn't get killed
+fn stop_pid(pid: Pid, process_name: &str) -> Stopped {
+    let matches = dist_utils::misc::with_running_process(pid, |process| {
+        process.name() == short_name(process_name)
+    });
+
+    if matches == Some(true) {
+        dist_utils::misc::stop_process(pid, DEFAULT_GRACE_PERIOD)
+    } else {
+        Stopped::NotRunning
+    }
 }
 
-impl From<ComponentListing> for ComponentManager {
-    fn from(listing: ComponentListing) -> Self {
+// Launches the component's process, detaching it from the terminal when there's a PID file to write
+fn spawn(mut command: Command, pid_file: Option<&Path>) -> Result<()> {
+    match pid_file {
+        Some(pid_file) => {
+            let child = command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .process_group(0)
+                .spawn()?;
+            pid_file::write(pid_file, child.id() as Pid)?;
+        }
+        None => {
+            command.spawn()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ComponentStatus {
+    Running { pids: Vec<Pid>, port: u16 },
+    Stopped,
+}
+
+impl fmt::Display for ComponentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running { pids, port } => {
+                let pids: Vec<_> = pids.iter().map(|pid| pid.to_string()).collect();
+                write!(f, "running (pid {}, port {})", pids.join(", "), port)
+            }
+            Self::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+impl ComponentManager {
+    pub fn new(listing: ComponentListing, config: &ServerConfig) -> Self {
         let mut components: Vec<Box<dyn Component>> = Vec::new();
 
         if !listing.no_seeder {
-            components.push(Box::new(Seeder));
+            components.push(Box::new(Seeder {
+                rpc_port: config.seeder_rpc_port,
+            }));
         }
-        if !listing.no_database {
-            components.push(Box::new(Database));
+        if !listing.no_http {
+            components.push(Box::new(HttpServer {
+                port: config.http_port,
+                seeder_rpc_port: config.seeder_rpc_port,
+                tracker_port: config.tracker_port,
+            }));
         }
         if !listing.no_tracker {
-            components.push(Box::new(Tracker));
+            components.push(Box::new(Tracker {
+                port: config.tracker_port,
+            }));
         }
 
         Self { components }
@@ -48,63 +183,257 @@ impl From<ComponentListing> for ComponentManager {
 }
 
 pub trait Component {
-    fn start(&self) -> Result<()>;
+    fn name(&self) -> &'static str;
+
+    /// Name of the process that gets run for the component
+    fn process_name(&self) -> &'static str;
+
+    fn port(&self) -> u16;
+
+    /// Starts the component as a daemon if given a PID file to write
+    fn start(&self, pid_file: Option<&Path>) -> Result<()>;
 
     fn stop(&self);
+
+    fn status(&self, processes: &dyn ProcessTable) -> ComponentStatus {
+        let pids = processes.pids(self.process_name());
+        if pids.is_empty() {
+            ComponentStatus::Stopped
+        } else {
+            ComponentStatus::Running {
+                pids,
+                port: self.port(),
+            }
+        }
+    }
+}
+
+pub struct Seeder {
+    rpc_port: u16,
 }
 
-pub struct Seeder;
+impl Seeder {
+    fn opts(&self) -> TransmissionOpts {
+        TransmissionOpts::new()
+            .download_dir(dist_utils::path::torrent_file_dir())
+            .port(self.rpc_port)
+    }
+}
 
 impl Component for Seeder {
-    fn start(&self) -> Result<()> {
+    fn name(&self) -> &'static str {
+        "seeder"
+    }
+
+    fn process_name(&self) -> &'static str {
+        SEEDER_NAME
+    }
+
+    fn port(&self) -> u16 {
+        self.rpc_port
+    }
+
+    fn start(&self, pid_file: Option<&Path>) -> Result<()> {
         info!("Starting seeder server");
-        Transmission::start(
-            TransmissionOpts::new().download_dir(dist_utils::path::torrent_file_dir()),
-        )?;
+        // `transmission-daemon` already forks into the background, so it only needs to be told
+        // where to write its PID file
+        let opts = match pid_file {
+            Some(pid_file) => self.opts().pid_file(pid_file.to_owned()),
+            None => self.opts(),
+        };
+        Transmission::start(opts)?;
 
         Ok(())
     }
 
     fn stop(&self) {
         info!("Shutting down seeder server");
-        if let Some(transmission) = Transmission::from_running(
-            TransmissionOpts::new().download_dir(dist_utils::path::torrent_file_dir()),
-        ) {
-            transmission.stop();
+        match Transmission::from_running(self.opts()) {
+            Ok(Some(transmission)) => {
+                transmission.stop();
+            }
+            Ok(None) => {}
+            Err(err) => warn!("Failed stopping the seeder server Error: {}", err),
         }
     }
 }
 
-pub struct Database;
+/// Serves the package database. The database itself only gets built by adding packages
+pub struct HttpServer {
+    port: u16,
+    // Only used for reporting on the other components at `/health`
+    seeder_rpc_port: u16,
+    tracker_port: u16,
+}
+
+impl Component for HttpServer {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn process_name(&self) -> &'static str {
+        HTTP_SERVER_NAME
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
 
-impl Component for Database {
-    fn start(&self) -> Result<()> {
-        info!("Starting database server");
-        Command::new(DATABASE_SERVER_NAME)
+    fn start(&self, pid_file: Option<&Path>) -> Result<()> {
+        info!("Starting HTTP server");
+        let mut command = Command::new(HTTP_SERVER_NAME);
+        command
             .arg("--socket")
-            .arg("0.0.0.0:9090")
-            .arg(&dist_utils::path::package_db_file())
-            .spawn()?;
-        Ok(())
+            .arg(format!("0.0.0.0:{}", self.port))
+            .arg("--seeder")
+            .arg(format!("127.0.0.1:{}", self.seeder_rpc_port))
+            .arg("--tracker")
+            .arg(format!("127.0.0.1:{}", self.tracker_port))
+            .arg(dist_utils::path::package_db_file())
+            .arg(dist_utils::path::package_db_signature_file());
+        spawn(command, pid_file)
     }
 
     fn stop(&self) {
-        info!("Shutting down database server");
-        dist_utils::misc::stop_process_by_name(DATABASE_SERVER_NAME);
+        info!("Shutting down HTTP server");
+        dist_utils::misc::stop_process_by_name(HTTP_SERVER_NAME, DEFAULT_GRACE_PERIOD);
     }
 }
 
-pub struct Tracker;
+pub struct Tracker {
+    port: u16,
+}
 
 impl Component for Tracker {
-    fn start(&self) -> Result<()> {
+    fn name(&self) -> &'static str {
+        "tracker"
+    }
+
+    fn process_name(&self) -> &'static str {
+        TRACKER_SERVER_NAME
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn start(&self, pid_file: Option<&Path>) -> Result<()> {
         info!("Starting tracker server");
-        Command::new(TRACKER_SERVER_NAME).spawn()?;
-        Ok(())
+        // Serve over both TCP and UDP on the same port
+        let port = self.port.to_string();
+        let mut command = Command::new(TRACKER_SERVER_NAME);
+        command.args(["-p", &port, "-P", &port]);
+        spawn(command, pid_file)
     }
 
     fn stop(&self) {
         info!("Shutting down tracker server");
-        dist_utils::misc::stop_process_by_name(TRACKER_SERVER_NAME);
+        dist_utils::misc::stop_process_by_name(TRACKER_SERVER_NAME, DEFAULT_GRACE_PERIOD);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    struct FakeProcesses(HashMap<&'static str, Vec<Pid>>);
+
+    impl ProcessTable for FakeProcesses {
+        fn pids(&self, name: &str) -> Vec<Pid> {
+            self.0.get(name).cloned().unwrap_or_default()
+        }
+    }
+
+    #[test]
+    fn component_status() {
+        let mut running = HashMap::new();
+        running.insert(SEEDER_NAME, vec![100]);
+        running.insert(HTTP_SERVER_NAME, vec![200, 201]);
+        let processes = FakeProcesses(running);
+
+        let config = ServerConfig {
+            http_port: 8080,
+            ..ServerConfig::default()
+        };
+        let manager = ComponentManager::new(
+            ComponentListing {
+                no_seeder: false,
+                no_http: false,
+                no_tracker: false,
+            },
+            &config,
+        );
+        let statuses = manager.status(&processes);
+        assert_eq!(
+            statuses,
+            [
+                (
+                    "seeder",
+                    ComponentStatus::Running {
+                        pids: vec![100],
+                        port: 9091
+                    }
+                ),
+                (
+                    "http",
+                    ComponentStatus::Running {
+                        pids: vec![200, 201],
+                        port: 8080
+                    }
+                ),
+                ("tracker", ComponentStatus::Stopped),
+            ]
+        );
+        assert_eq!(
+            statuses[1].1.to_string(),
+            "running (pid 200, 201, port 8080)"
+        );
+        assert_eq!(statuses[2].1.to_string(), "stopped");
+
+        // Ignored components are left out
+        let manager = ComponentManager::new(
+            ComponentListing {
+                no_seeder: true,
+                no_http: true,
+                no_tracker: false,
+            },
+            &config,
+        );
+        assert_eq!(
+            manager.status(&processes),
+            [("tracker", ComponentStatus::Stopped)]
+        );
+    }
+
+    #[test]
+    fn component_listing() {
+        let config = ServerConfig::default();
+        for combination in 0..8 {
+            let listing = ComponentListing {
+                no_seeder: combination & 1 != 0,
+                no_http: combination & 2 != 0,
+                no_tracker: combination & 4 != 0,
+            };
+            let mut expected = Vec::new();
+            if !listing.no_seeder {
+                expected.push("seeder");
+            }
+            if !listing.no_http {
+                expected.push("http");
+            }
+            if !listing.no_tracker {
+                expected.push("tracker");
+            }
+
+            let manager = ComponentManager::new(listing, &config);
+            let names: Vec<_> = manager
+                .components
+                .iter()
+                .map(|component| component.name())
+                .collect();
+            assert_eq!(names, expected);
+        }
     }
 }
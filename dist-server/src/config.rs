@@ -1,7 +1,7 @@
 use anyhow::Result;
 use serde::Deserialize;
 
-use std::fs::File;
+use std::{fs::File, io, path::PathBuf};
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
@@ -16,3 +16,67 @@ impl Config {
         Ok(config)
     }
 }
+
+/// The ports used by each of the server components. These live in the same file as [`Config`], but
+/// everything has a default so the file is optional for just running the components
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub http_port: u16,
+    pub tracker_port: u16,
+    pub seeder_rpc_port: u16,
+    /// A file with the base64 encoded ed25519 key used to sign the package database
+    pub signing_key: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            http_port: 9090,
+            tracker_port: 6969,
+            seeder_rpc_port: 9091,
+            signing_key: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn try_new() -> Result<Self> {
+        let config_path = dist_utils::path::server_config_file();
+        let config_file = match File::open(&config_path) {
+            Ok(config_file) => config_file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let config = serde_yaml::from_reader(config_file)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_ports() -> Result<()> {
+        let config: ServerConfig = serde_yaml::from_str(
+            "announce_url: http://tracker.example.com/announce\nhttp_port: 8080\ntracker_port: 7070",
+        )?;
+        assert_eq!(
+            config,
+            ServerConfig {
+                http_port: 8080,
+                tracker_port: 7070,
+                ..ServerConfig::default()
+            }
+        );
+
+        // Everything falls back to the defaults
+        let config: ServerConfig =
+            serde_yaml::from_str("announce_url: http://tracker.example.com")?;
+        assert_eq!(config, ServerConfig::default());
+        assert_eq!(config.seeder_rpc_port, 9091);
+
+        Ok(())
+    }
+}
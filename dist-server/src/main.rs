@@ -3,15 +3,17 @@ use clap::Clap;
 use log::{debug, info};
 
 use crate::{
-    cli::{AddPackage, Opts, SubCommand},
-    components::ComponentManager,
-    packages::add_packages,
+    cli::{AddPackage, Opts, RemovePackage, StartComponents, SubCommand},
+    components::{ComponentManager, SystemProcesses},
+    config::ServerConfig,
+    packages::{add_packages, remove_packages},
 };
 
 mod cli;
 mod components;
 mod config;
 mod packages;
+mod pid_file;
 
 fn main() -> Result<()> {
     let Opts {
@@ -30,15 +32,31 @@ fn main() -> Result<()> {
     dist_utils::path::create_dirs(dist_utils::Mode::Server)?;
 
     match subcmd {
-        SubCommand::Start(component_listing) => {
-            ComponentManager::from(component_listing).start()?;
+        SubCommand::Start(StartComponents { daemon, components }) => {
+            ComponentManager::new(components, &ServerConfig::try_new()?).start(daemon)?;
         }
         SubCommand::Stop(component_listing) => {
-            ComponentManager::from(component_listing).stop();
+            ComponentManager::new(component_listing, &ServerConfig::try_new()?).stop();
         }
-        SubCommand::Add(AddPackage { package_paths }) => {
+        SubCommand::Status(component_listing) => {
+            let processes = SystemProcesses::new();
+            for (name, status) in
+                ComponentManager::new(component_listing, &ServerConfig::try_new()?)
+                    .status(&processes)
+            {
+                println!("{:<8}  {}", name, status);
+            }
+        }
+        SubCommand::Add(AddPackage {
+            package_paths,
+            print_magnets,
+        }) => {
             info!("Adding packages: {:#?}", package_paths);
-            add_packages(package_paths)?;
+            add_packages(package_paths, print_magnets)?;
+        }
+        SubCommand::Remove(RemovePackage { names, purge }) => {
+            info!("Removing packages: {:#?}", names);
+            remove_packages(names, purge)?;
         }
     }
 
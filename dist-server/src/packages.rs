@@ -1,19 +1,117 @@
-use anyhow::Result;
-use dist_package::{AddedPackage, NewPackage};
-use dist_package_db::database::{DistpacDB, MissingDBAction};
-use transmission_wrapper::{Transmission, TransmissionOpts};
+use anyhow::{Context, Result};
+use dist_package::{checksum, signature, AddedPackage, NewPackage};
+use dist_package_db::{
+    database::{DistpacDB, MissingDBAction},
+    models::PackageEntry,
+};
+use dist_utils::misc::TORRENT_LABEL;
+use log::{info, warn};
+use transmission_wrapper::{bytes::Bytes, error::Error, Transmission, TransmissionOpts};
 
-use std::path::PathBuf;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
-use crate::config::Config;
+use crate::config::{Config, ServerConfig};
 
-pub fn add_packages(package_paths: Vec<PathBuf>) -> Result<()> {
+/// Anything that can seed the torrents for the served packages
+pub trait Seed {
+    fn seed(&self, torrent_file: &Path) -> Result<u64, Error>;
+    /// Stops seeding the torrent, returning whether it was being seeded at all
+    fn unseed(&mut self, torrent_name: &str) -> Result<bool, Error>;
+}
+
+impl Seed for Transmission {
+    fn seed(&self, torrent_file: &Path) -> Result<u64, Error> {
+        self.seed_local_torrent(torrent_file)
+    }
+
+    fn unseed(&mut self, torrent_name: &str) -> Result<bool, Error> {
+        self.refresh()?;
+        match self.get_by_name(torrent_name).map(|entry| *entry.id()) {
+            Some(id) => self.remove_torrent(id).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Where the added packages and their torrents get stored
+pub struct PackageDirs {
+    pub data_dir: PathBuf,
+    pub torrent_dir: PathBuf,
+}
+
+pub fn add_packages(package_paths: Vec<PathBuf>, print_magnets: bool) -> Result<()> {
     let Config { announce_url } = Config::try_new()?;
+    let ServerConfig { signing_key, .. } = ServerConfig::try_new()?;
     let package_db = DistpacDB::connect(
         &dist_utils::path::package_db_file(),
         MissingDBAction::Create,
     )?;
+    let dirs = PackageDirs {
+        data_dir: dist_utils::path::torrent_data_dir(),
+        torrent_dir: dist_utils::path::torrent_file_dir(),
+    };
+
+    let transmission = Transmission::start(
+        TransmissionOpts::new()
+            .download_dir(dirs.data_dir.clone())
+            .labels(vec![TORRENT_LABEL.to_owned()]),
+    )?;
+    let added = add_packages_with(
+        &package_db,
+        &transmission,
+        &dirs,
+        &announce_url,
+        package_paths,
+    )?;
+
+    if let Some(signing_key) = signing_key {
+        sign_package_db(
+            &dist_utils::path::package_db_file(),
+            &signing_key,
+            &dist_utils::path::package_db_signature_file(),
+        )?;
+    }
+
+    if print_magnets {
+        write_magnets(&added, io::stdout().lock())?;
+    }
+
+    Ok(())
+}
+
+/// Writes a `<magnet>\t<name>` line for each entry so that the torrents can be published elsewhere
+pub fn write_magnets(entries: &[PackageEntry], mut writer: impl Write) -> io::Result<()> {
+    for entry in entries {
+        writeln!(writer, "{}\t{}", entry.magnet(), entry.name())?;
+    }
+
+    writer.flush()
+}
+
+/// Writes a detached signature of the package database for clients to verify it against
+pub fn sign_package_db(db_file: &Path, signing_key: &Path, signature_file: &Path) -> Result<()> {
+    let private_key = fs::read_to_string(signing_key)
+        .with_context(|| format!("Failed reading the signing key {}", signing_key.display()))?;
+    let signature = signature::sign(&private_key, &fs::read(db_file)?)?;
+    fs::write(signature_file, format!("{}\n", signature))?;
+    info!("Signed the package database");
 
+    Ok(())
+}
+
+/// Turns each package into a torrent that gets seeded and listed in the package database,
+/// returning the added entries
+pub fn add_packages_with(
+    package_db: &DistpacDB,
+    seeder: &impl Seed,
+    dirs: &PackageDirs,
+    announce_url: &str,
+    package_paths: Vec<PathBuf>,
+) -> Result<Vec<PackageEntry>> {
     // Validate all the new packages first
     let mut new_packages = Vec::with_capacity(package_paths.len());
     for package_path in package_paths.into_iter() {
@@ -25,20 +123,488 @@ pub fn add_packages(package_paths: Vec<PathBuf>) -> Result<()> {
     for new_package in new_packages.into_iter() {
         added_packages.push(AddedPackage::new(
             new_package,
-            dist_utils::path::torrent_data_dir(),
-            dist_utils::path::torrent_file_dir(),
-            &announce_url,
+            dirs.data_dir.clone(),
+            dirs.torrent_dir.clone(),
+            announce_url,
         )?);
     }
 
-    // then add each package to the database and start seeding them
-    let transmission = Transmission::start(
-        TransmissionOpts::new().download_dir(dist_utils::path::torrent_data_dir()),
+    // Build all of the entries before seeding anything so that an invalid one doesn't leave
+    // torrents seeding without a database entry
+    let mut entries = Vec::with_capacity(added_packages.len());
+    let mut torrent_paths = Vec::with_capacity(added_packages.len());
+    for AddedPackage {
+        name,
+        version,
+        description,
+        installed_path,
+        torrent,
+    } in added_packages.into_iter()
+    {
+        // Hashed the same way the client checks its downloads
+        let sha256 = checksum::sha256(&installed_path)?;
+
+        // `imdl` leaves a trailing newline on the magnet link
+        let mut builder = PackageEntry::builder()
+            .name(name)
+            .version(version)
+            .magnet(torrent.magnet.trim().to_owned())
+            .size(torrent.size)
+            .sha256(sha256);
+        if let Some(description) = description {
+            builder = builder.description(description);
+        }
+        entries.push(builder.build()?);
+        torrent_paths.push(torrent.path);
+    }
+
+    // then start seeding each package and add them all to the database at once
+    for (entry, torrent_path) in entries.iter().zip(&torrent_paths) {
+        seeder.seed(torrent_path)?;
+        info!(
+            "Added {} {} ({})",
+            entry.name(),
+            entry.version(),
+            Bytes::from(*entry.size())
+        );
+    }
+    package_db.add_package_entries(entries.clone())?;
+
+    Ok(entries)
+}
+
+pub fn remove_packages(names: Vec<String>, purge: bool) -> Result<()> {
+    let package_db = DistpacDB::connect(
+        &dist_utils::path::package_db_file(),
+        MissingDBAction::RaiseError,
     )?;
-    for added_package in added_packages.into_iter() {
-        transmission.seed_local_torrent(&added_package.torrent.path)?;
-        package_db.add_package(added_package)?;
+    let dirs = PackageDirs {
+        data_dir: dist_utils::path::torrent_data_dir(),
+        torrent_dir: dist_utils::path::torrent_file_dir(),
+    };
+
+    let mut transmission =
+        Transmission::start(TransmissionOpts::new().download_dir(dirs.data_dir.clone()))?;
+    remove_packages_with(&package_db, &mut transmission, &dirs, &names, purge)?;
+
+    // The old signature won't match the database anymore
+    match ServerConfig::try_new()?.signing_key {
+        Some(signing_key) => sign_package_db(
+            &dist_utils::path::package_db_file(),
+            &signing_key,
+            &dist_utils::path::package_db_signature_file(),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Drops every version of each package from the database and stops seeding their torrents
+///
+/// With `purge` the torrent files and the package data get deleted too
+pub fn remove_packages_with(
+    package_db: &DistpacDB,
+    seeder: &mut impl Seed,
+    dirs: &PackageDirs,
+    names: &[String],
+    purge: bool,
+) -> Result<()> {
+    // Check that all the packages exist first
+    let all_entries = package_db.list_all()?;
+    for name in names {
+        if !all_entries.iter().any(|entry| entry.name() == name) {
+            anyhow::bail!("No package entry found for: {}", name);
+        }
+    }
+
+    for name in names {
+        for entry in all_entries.iter().filter(|entry| entry.name() == name) {
+            let torrent_name = entry.torrent_name();
+            if !seeder.unseed(torrent_name)? {
+                warn!("{} wasn't being seeded", torrent_name);
+            }
+
+            if purge {
+                let torrent_file = dirs.torrent_dir.join(format!("{}.torrent", torrent_name));
+                if torrent_file.exists() {
+                    fs::remove_file(torrent_file)?;
+                }
+                let data_dir = dirs.data_dir.join(torrent_name);
+                if data_dir.exists() {
+                    fs::remove_dir_all(data_dir)?;
+                }
+            }
+        }
+
+        package_db.remove_by_name(name)?;
+        info!("Removed {}", name);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dist_package::manifest::Version;
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use sha2::{Digest, Sha256};
+
+    use std::{cell::RefCell, env, os::unix::fs::PermissionsExt, sync::Once};
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    // Stands in for `imdl` so that no real torrents need to be made
+    const FAKE_IMDL: &str = r#"#!/bin/sh
+case "$2" in
+    create)
+        shift 2
+        while [ $# -gt 0 ]; do
+            case "$1" in
+                --output) output="$2"; shift 2 ;;
+                --announce) shift 2 ;;
+                *) shift ;;
+            esac
+        done
+        touch "$output"
+        ;;
+    link)
+        case "$3" in
+            *badmagnet*) echo "magnet:?xt=urn:btih:badmagnet" ;;
+            *) echo "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567" ;;
+        esac
+        ;;
+    show)
+        name=$(basename "$4" .torrent)
+        echo "{\"name\": \"$name\", \"info_hash\": \"0123456789abcdef\", \"content_size\": 1000}"
+        ;;
+esac
+"#;
+
+    #[derive(Default)]
+    struct FakeSeeder {
+        seeded: RefCell<Vec<PathBuf>>,
+    }
+
+    impl Seed for FakeSeeder {
+        fn seed(&self, torrent_file: &Path) -> Result<u64, Error> {
+            let mut seeded = self.seeded.borrow_mut();
+            seeded.push(torrent_file.to_owned());
+            Ok(seeded.len() as u64)
+        }
+
+        fn unseed(&mut self, torrent_name: &str) -> Result<bool, Error> {
+            let mut seeded = self.seeded.borrow_mut();
+            let before = seeded.len();
+            seeded.retain(|torrent_file| {
+                torrent_file.file_stem().and_then(|stem| stem.to_str()) != Some(torrent_name)
+            });
+            Ok(seeded.len() != before)
+        }
+    }
+
+    // Puts the fake `imdl` on the `PATH` once for all the tests since they share the environment
+    fn install_fake_imdl() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            let bin_dir = tempfile::tempdir()
+                .expect("Failed creating bin dir")
+                .into_path();
+            let imdl = bin_dir.join("imdl");
+            fs::write(&imdl, FAKE_IMDL).expect("Failed writing fake imdl");
+            fs::set_permissions(&imdl, fs::Permissions::from_mode(0o755))
+                .expect("Failed making fake imdl executable");
+            let path = env::var_os("PATH").unwrap_or_default();
+            env::set_var(
+                "PATH",
+                env::join_paths(Some(bin_dir).into_iter().chain(env::split_paths(&path)))
+                    .expect("Invalid PATH"),
+            );
+        });
+    }
+
+    fn package_dirs(temp_dir: &Path) -> BoxResult<PackageDirs> {
+        let dirs = PackageDirs {
+            data_dir: temp_dir.join("data"),
+            torrent_dir: temp_dir.join("torrents"),
+        };
+        fs::create_dir(&dirs.data_dir)?;
+        fs::create_dir(&dirs.torrent_dir)?;
+
+        Ok(dirs)
+    }
+
+    fn create_package(dir: &Path, name: &str, version: &str) -> BoxResult<PathBuf> {
+        create_described_package(dir, name, version, None)
+    }
+
+    fn create_described_package(
+        dir: &Path,
+        name: &str,
+        version: &str,
+        description: Option<&str>,
+    ) -> BoxResult<PathBuf> {
+        let package_dir = dir.join(name);
+        fs::create_dir_all(package_dir.join("assets"))?;
+        fs::create_dir_all(package_dir.join("scripts"))?;
+        let mut manifest = format!("name: {}\nversion: {}\n", name, version);
+        if let Some(description) = description {
+            manifest.push_str(&format!("description: {}\n", description));
+        }
+        fs::write(package_dir.join("manifest.yaml"), manifest)?;
+        fs::write(package_dir.join("scripts").join("install.sh"), "")?;
+        fs::write(package_dir.join("scripts").join("uninstall.sh"), "")?;
+
+        Ok(package_dir)
+    }
+
+    #[test]
+    fn add_package() -> BoxResult<()> {
+        install_fake_imdl();
+        let temp_dir = tempfile::tempdir()?;
+        let dirs = package_dirs(temp_dir.path())?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        let package_path = create_described_package(
+            temp_dir.path(),
+            "ripgrep",
+            "12.1.1",
+            Some("Recursively search directories for a regex"),
+        )?;
+        fs::write(package_path.join("assets").join("rg"), "ripgrep binary")?;
+        let seeder = FakeSeeder::default();
+
+        add_packages_with(
+            &package_db,
+            &seeder,
+            &dirs,
+            "http://tracker.example.com/announce",
+            vec![package_path],
+        )?;
+
+        let entry = package_db
+            .query("ripgrep")?
+            .ok_or("Missing package entry")?;
+        assert_eq!(entry.version(), &Version::new(12, 1, 1));
+        assert_eq!(entry.torrent_name(), "ripgrep-12.1.1");
+        assert_eq!(
+            entry.magnet(),
+            "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567"
+        );
+        assert_eq!(entry.size(), &1000);
+        assert_eq!(
+            entry.description().as_deref(),
+            Some("Recursively search directories for a regex")
+        );
+        // Each file's relative path and contents in sorted order
+        let mut hasher = Sha256::new();
+        for (path, contents) in [
+            ("assets/rg", "ripgrep binary"),
+            (
+                "manifest.yaml",
+                "name: ripgrep\nversion: 12.1.1\ndescription: Recursively search directories for a regex\n",
+            ),
+            ("scripts/install.sh", ""),
+            ("scripts/uninstall.sh", ""),
+        ] {
+            hasher.update(path);
+            hasher.update([0]);
+            hasher.update(contents);
+            hasher.update([0]);
+        }
+        assert_eq!(
+            entry.sha256().as_deref(),
+            Some(format!("{:x}", hasher.finalize()).as_str())
+        );
+        assert!(dirs.data_dir.join("ripgrep-12.1.1").is_dir());
+        assert_eq!(
+            *seeder.seeded.borrow(),
+            [dirs.torrent_dir.join("ripgrep-12.1.1.torrent")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn printed_magnets() -> BoxResult<()> {
+        install_fake_imdl();
+        let temp_dir = tempfile::tempdir()?;
+        let dirs = package_dirs(temp_dir.path())?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        let package_paths = vec![
+            create_package(temp_dir.path(), "ripgrep", "12.1.1")?,
+            create_package(temp_dir.path(), "fd", "8.2.1")?,
+        ];
+
+        let added = add_packages_with(
+            &package_db,
+            &FakeSeeder::default(),
+            &dirs,
+            "http://tracker.example.com/announce",
+            package_paths,
+        )?;
+        let mut printed = Vec::new();
+        write_magnets(&added, &mut printed)?;
+
+        let mut printed: Vec<_> = String::from_utf8(printed)?
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        printed.sort();
+        let mut expected: Vec<_> = package_db
+            .list_all()?
+            .iter()
+            .map(|entry| format!("{}\t{}", entry.magnet(), entry.name()))
+            .collect();
+        expected.sort();
+        assert_eq!(printed.len(), 2);
+        assert_eq!(printed, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_entry_seeds_nothing() -> BoxResult<()> {
+        install_fake_imdl();
+        let temp_dir = tempfile::tempdir()?;
+        let dirs = package_dirs(temp_dir.path())?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        let package_paths = vec![
+            create_package(temp_dir.path(), "ripgrep", "12.1.1")?,
+            create_package(temp_dir.path(), "badmagnet", "1.0.0")?,
+        ];
+        let seeder = FakeSeeder::default();
+
+        assert!(add_packages_with(
+            &package_db,
+            &seeder,
+            &dirs,
+            "http://tracker.example.com/announce",
+            package_paths,
+        )
+        .is_err());
+        assert!(seeder.seeded.borrow().is_empty());
+        assert!(package_db.list_all()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn readd_package() -> BoxResult<()> {
+        install_fake_imdl();
+        let temp_dir = tempfile::tempdir()?;
+        let dirs = package_dirs(temp_dir.path())?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+
+        // Adding the same package again just replaces its entry
+        for _ in 0..2 {
+            // The package gets moved into the data dir when it's added
+            let package_path = create_package(temp_dir.path(), "ripgrep", "12.1.1")?;
+            add_packages_with(
+                &package_db,
+                &FakeSeeder::default(),
+                &dirs,
+                "http://tracker.example.com/announce",
+                vec![package_path],
+            )?;
+        }
+        let entries = package_db.list_all()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].version(), &Version::new(12, 1, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn signed_package_db() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_file = temp_dir.path().join("packages.db");
+        let package_db = DistpacDB::connect(&db_file, MissingDBAction::Create)?;
+        package_db.add_package_entry(PackageEntry::new(
+            "ripgrep".to_owned(),
+            Version::new(12, 1, 1),
+            "magnet:?xt=urn:btih:ripgrep".to_owned(),
+            1_000,
+        ))?;
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| "Failed generating key")?;
+        let signing_key = temp_dir.path().join("signing.key");
+        fs::write(&signing_key, base64::encode(pkcs8.as_ref()))?;
+        let public_key = base64::encode(
+            Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                .map_err(|_| "Invalid key")?
+                .public_key(),
+        );
+
+        let signature_file = temp_dir.path().join("packages.db.sig");
+        sign_package_db(&db_file, &signing_key, &signature_file)?;
+        signature::verify(
+            &public_key,
+            &fs::read(&db_file)?,
+            &fs::read_to_string(&signature_file)?,
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_package() -> BoxResult<()> {
+        install_fake_imdl();
+        let temp_dir = tempfile::tempdir()?;
+        let dirs = package_dirs(temp_dir.path())?;
+        let package_db = DistpacDB::connect(
+            &temp_dir.path().join("packages.db"),
+            MissingDBAction::Create,
+        )?;
+        let package_paths = vec![
+            create_package(temp_dir.path(), "ripgrep", "12.1.1")?,
+            create_package(temp_dir.path(), "fd", "8.2.1")?,
+        ];
+        let mut seeder = FakeSeeder::default();
+        add_packages_with(
+            &package_db,
+            &seeder,
+            &dirs,
+            "http://tracker.example.com/announce",
+            package_paths,
+        )?;
+
+        // Unknown packages stop anything from getting removed
+        let names = vec!["ripgrep".to_owned(), "missing".to_owned()];
+        assert!(remove_packages_with(&package_db, &mut seeder, &dirs, &names, false).is_err());
+        assert_eq!(package_db.list_all()?.len(), 2);
+
+        remove_packages_with(&package_db, &mut seeder, &dirs, &names[..1], false)?;
+        assert!(package_db.query("ripgrep")?.is_none());
+        assert!(package_db.query("fd")?.is_some());
+        assert_eq!(
+            *seeder.seeded.borrow(),
+            [dirs.torrent_dir.join("fd-8.2.1.torrent")]
+        );
+        // The data is kept around without purging
+        assert!(dirs.data_dir.join("ripgrep-12.1.1").is_dir());
+
+        remove_packages_with(&package_db, &mut seeder, &dirs, &["fd".to_owned()], true)?;
+        assert!(package_db.list_all()?.is_empty());
+        assert!(seeder.seeded.borrow().is_empty());
+        assert!(!dirs.data_dir.join("fd-8.2.1").exists());
+        assert!(!dirs.torrent_dir.join("fd-8.2.1.torrent").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use sysinfo::Pid;
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+pub fn write(path: &Path, pid: Pid) -> io::Result<()> {
+    fs::write(path, format!("{}\n", pid))
+}
+
+/// Reads the process id from the PID file, or `None` if there isn't one
+pub fn read(path: &Path) -> io::Result<Option<Pid>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    contents.trim().parse().map(Some).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid PID file {}", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn round_trip() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("tracker.pid");
+
+        assert_eq!(read(&path)?, None);
+        write(&path, 4321)?;
+        assert_eq!(read(&path)?, Some(4321));
+        // `transmission-daemon` writes its own PID files with a trailing newline
+        assert_eq!(fs::read_to_string(&path)?, "4321\n");
+
+        fs::write(&path, "not a pid")?;
+        assert_eq!(read(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+use std::{io, path::PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed creating directory {} Error: {source}", path.display())]
+pub struct CreateDirError {
+    pub path: PathBuf,
+    #[source]
+    pub source: io::Error,
+}
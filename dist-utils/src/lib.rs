@@ -1,3 +1,4 @@
+pub mod error;
 pub mod misc;
 pub mod path;
 
@@ -5,4 +6,12 @@ pub mod path;
 pub enum Mode {
     Client,
     Server,
+    /// For a machine that runs both the client and the server
+    Both,
+}
+
+impl Mode {
+    pub fn is_server(self) -> bool {
+        matches!(self, Self::Server | Self::Both)
+    }
 }
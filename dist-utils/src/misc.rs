@@ -1,13 +1,135 @@
-use sysinfo::{ProcessExt, Signal, System, SystemExt};
+use sysinfo::{Pid, Process, ProcessExt, ProcessStatus, Signal, System, SystemExt};
 
-pub fn stop_process_by_name(name: &str) {
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long a process gets to shut down on its own before it gets killed
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The label put on every torrent distpac adds so they can be told apart in transmission
+pub const TORRENT_LABEL: &str = "distpac";
+
+/// How a process ended up stopping, ordered from least to most drastic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stopped {
+    NotRunning,
+    Gracefully,
+    Forcefully,
+}
+
+/// Stops every process with the name, returning the most drastic way that one had to be stopped
+pub fn stop_process_by_name(name: &str, grace_period: Duration) -> Stopped {
     let mut system = System::new();
     system.refresh_all();
     // Name is truncated here and I don't feel like trying to snag it from the command path
     let name = if name.len() < 15 { name } else { &name[..15] };
-    let processes = system.get_process_by_name(name);
+    let pids: Vec<_> = system
+        .get_process_by_name(name)
+        .iter()
+        .map(|process| process.pid())
+        .collect();
+
+    pids.into_iter()
+        .map(|pid| stop_process(pid, grace_period))
+        .max()
+        .unwrap_or(Stopped::NotRunning)
+}
+
+/// Asks the process to stop with `SIGTERM`, only resorting to `SIGKILL` if it's still running after
+/// the grace period. Killing it outright can leave things like transmission's resume files in a bad
+/// state
+pub fn stop_process(pid: Pid, grace_period: Duration) -> Stopped {
+    let terminated = with_running_process(pid, |process| process.kill(Signal::Term));
+    if terminated != Some(true) {
+        return Stopped::NotRunning;
+    }
+
+    let start = Instant::now();
+    while start.elapsed() < grace_period {
+        thread::sleep(POLL_INTERVAL);
+        if with_running_process(pid, |_| ()).is_none() {
+            return Stopped::Gracefully;
+        }
+    }
+
+    with_running_process(pid, |process| process.kill(Signal::Kill));
+    Stopped::Forcefully
+}
+
+/// Calls `f` with the process if it's still running. A process that exited without being reaped yet
+/// sticks around as a zombie, but that doesn't count
+pub fn with_running_process<T>(pid: Pid, f: impl FnOnce(&Process) -> T) -> Option<T> {
+    // `refresh_process()`'s return value can't be trusted for a fresh `System` and a stale entry
+    // never gets removed when the process goes away, so this starts over every time
+    let mut system = System::new();
+    system.refresh_process(pid);
+    system
+        .get_process(pid)
+        .filter(|process| !matches!(process.status(), ProcessStatus::Zombie))
+        .map(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+    };
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    // Waits for the script to finish setting up its trap before handing it back
+    fn spawn_trapping(trap: &str) -> BoxResult<Child> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "trap {} TERM; echo ready; while true; do sleep 0.05; done",
+                trap
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().ok_or("Missing stdout")?).read_line(&mut line)?;
+        assert_eq!(line, "ready\n");
+
+        Ok(child)
+    }
+
+    #[test]
+    fn graceful_stop() -> BoxResult<()> {
+        let mut child = spawn_trapping("'exit 0'")?;
+        let pid = child.id() as Pid;
+        assert_eq!(
+            stop_process(pid, Duration::from_secs(5)),
+            Stopped::Gracefully
+        );
+        assert!(child.wait()?.success());
+
+        // Nothing left to stop
+        assert_eq!(
+            stop_process(pid, Duration::from_secs(5)),
+            Stopped::NotRunning
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn forced_stop() -> BoxResult<()> {
+        // Ignores `SIGTERM` entirely
+        let mut child = spawn_trapping("''")?;
+        assert_eq!(
+            stop_process(child.id() as Pid, Duration::from_millis(200)),
+            Stopped::Forcefully
+        );
+        assert!(!child.wait()?.success());
 
-    for process in processes {
-        process.kill(Signal::Interrupt);
+        Ok(())
     }
 }
@@ -1,26 +1,76 @@
-use std::{fs, io, path::PathBuf};
+use std::{
+    env,
+    fs::DirBuilder,
+    io,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+};
 
-use crate::Mode;
+use crate::{error::CreateDirError, Mode};
 
-pub fn create_dirs(mode: Mode) -> io::Result<()> {
-    fs::create_dir_all(&database_dir())?;
-    fs::create_dir_all(&torrent_data_dir())?;
+/// Overrides where everything other than the config files gets stored
+pub const DATA_DIR_ENV: &str = "DISTPAC_DATA_DIR";
+/// Overrides where the config files get read from
+pub const CONFIG_DIR_ENV: &str = "DISTPAC_CONFIG_DIR";
+
+/// Creates any of the dirs that are missing, so it's fine to call on every run
+pub fn create_dirs(mode: Mode) -> Result<(), CreateDirError> {
+    create_dirs_in(&base_dir(), mode)?;
+    if mode.is_server() {
+        create_dir(&runtime_dir())?;
+    }
+
+    Ok(())
+}
+
+/// Creates the same layout as [`create_dirs`] under `root` instead of [`base_dir`], leaving out the
+/// runtime dir since that lives elsewhere
+pub fn create_dirs_in(root: &Path, mode: Mode) -> Result<(), CreateDirError> {
+    let torrent_dir = root.join("torrents");
+    create_dir(&root.join("databases"))?;
+    create_dir(&torrent_dir.join("data"))?;
 
     // Only the server has a separate location for torrent files. The client will just download
     // everything into the `torrent_data_dir`
-    if let Mode::Server = mode {
-        fs::create_dir_all(&torrent_file_dir())?;
+    if mode.is_server() {
+        create_dir(&torrent_dir.join("file"))?;
     }
 
     Ok(())
 }
 
+// Along with any missing parents. Only the current user gets access to anything new since the
+// databases and config are nobody else's business
+fn create_dir(path: &Path) -> Result<(), CreateDirError> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+        .or_else(|err| {
+            // Something that isn't a dir being in the way is still an error
+            if err.kind() == io::ErrorKind::AlreadyExists && path.is_dir() {
+                Ok(())
+            } else {
+                Err(err)
+            }
+        })
+        .map_err(|source| CreateDirError {
+            path: path.to_owned(),
+            source,
+        })
+}
+
 pub fn server_config_file() -> PathBuf {
-    base_dir().join("server.yaml")
+    config_dir().join("server.yaml")
 }
 
 pub fn client_config_file() -> PathBuf {
-    base_dir().join("client.yaml")
+    config_dir().join("client.yaml")
+}
+
+/// The config files live alongside everything else unless `DISTPAC_CONFIG_DIR` says otherwise
+pub fn config_dir() -> PathBuf {
+    env_dir(CONFIG_DIR_ENV).unwrap_or_else(base_dir)
 }
 
 pub fn installed_db_file() -> PathBuf {
@@ -31,6 +81,27 @@ pub fn package_db_file() -> PathBuf {
     database_dir().join("packages.db")
 }
 
+/// The detached signature the server publishes alongside the package database
+pub fn package_db_signature_file() -> PathBuf {
+    database_dir().join("packages.db.sig")
+}
+
+/// The client keeps a package database for each server it has synced with here
+pub fn package_cache_dir() -> PathBuf {
+    database_dir().join("cache")
+}
+
+/// Where the server keeps the PID files for daemonized components
+pub fn runtime_dir() -> PathBuf {
+    dirs_next::runtime_dir()
+        .map(|dir| dir.join("distpac"))
+        .unwrap_or_else(|| base_dir().join("run"))
+}
+
+pub fn pid_file(component: &str) -> PathBuf {
+    runtime_dir().join(format!("{}.pid", component))
+}
+
 pub fn database_dir() -> PathBuf {
     base_dir().join("databases")
 }
@@ -47,8 +118,107 @@ pub fn torrent_dir() -> PathBuf {
     base_dir().join("torrents")
 }
 
+/// Either `DISTPAC_DATA_DIR` or `distpac` within the platform's data dir, which respects
+/// `XDG_DATA_HOME` on Linux
 pub fn base_dir() -> PathBuf {
-    dirs_next::data_dir()
-        .expect("Failed getting data dir")
-        .join("distpac")
+    env_dir(DATA_DIR_ENV).unwrap_or_else(|| {
+        dirs_next::data_dir()
+            .expect("Failed getting data dir")
+            .join("distpac")
+    })
+}
+
+// An empty value is treated the same as it being unset
+fn env_dir(key: &str) -> Option<PathBuf> {
+    env::var_os(key)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn both_modes_dirs() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path();
+
+        create_dirs_in(&root.join("client"), Mode::Client)?;
+        assert!(root.join("client/databases").is_dir());
+        assert!(root.join("client/torrents/data").is_dir());
+        assert!(!root.join("client/torrents/file").exists());
+
+        create_dirs_in(&root.join("both"), Mode::Both)?;
+        for dir in ["databases", "torrents/data", "torrents/file"] {
+            assert!(root.join("both").join(dir).is_dir(), "Missing {}", dir);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_create_dirs() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("distpac");
+        fs::create_dir_all(root.join("databases"))?;
+
+        create_dirs_in(&root, Mode::Server)?;
+        create_dirs_in(&root, Mode::Server)?;
+        let mode = fs::metadata(root.join("torrents/file"))?
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // A file in the way gets named in the error
+        let blocked = temp_dir.path().join("blocked");
+        fs::create_dir_all(blocked.join("torrents"))?;
+        fs::write(blocked.join("torrents/data"), "")?;
+        let err = create_dirs_in(&blocked, Mode::Client).unwrap_err();
+        assert_eq!(err.path, blocked.join("torrents/data"));
+
+        Ok(())
+    }
+
+    // All in one test since the environment is shared by every test running at the same time
+    #[test]
+    fn env_overrides() {
+        let data_dir = Path::new("/tmp/distpac-data");
+        let config_dir = Path::new("/tmp/distpac-config");
+
+        env::set_var(DATA_DIR_ENV, data_dir);
+        env::remove_var(CONFIG_DIR_ENV);
+        assert_eq!(
+            package_db_file(),
+            data_dir.join("databases").join("packages.db")
+        );
+        assert_eq!(
+            installed_db_file(),
+            data_dir.join("databases").join("installed.db")
+        );
+        assert_eq!(torrent_data_dir(), data_dir.join("torrents").join("data"));
+        // Config falls back to the data dir
+        assert_eq!(client_config_file(), data_dir.join("client.yaml"));
+
+        env::set_var(CONFIG_DIR_ENV, config_dir);
+        assert_eq!(client_config_file(), config_dir.join("client.yaml"));
+        assert_eq!(server_config_file(), config_dir.join("server.yaml"));
+        assert_eq!(
+            package_db_file(),
+            data_dir.join("databases").join("packages.db")
+        );
+
+        // Empty values are ignored
+        env::set_var(DATA_DIR_ENV, "");
+        env::set_var(CONFIG_DIR_ENV, "");
+        assert_ne!(base_dir(), Path::new(""));
+        assert_eq!(client_config_file(), base_dir().join("client.yaml"));
+
+        env::remove_var(DATA_DIR_ENV);
+        env::remove_var(CONFIG_DIR_ENV);
+    }
 }
@@ -1,7 +1,20 @@
+use async_std::{fs, io, net::TcpStream};
 use clap::Clap;
-use log::debug;
+use log::{debug, warn};
+use tide::{
+    convert::json,
+    http::headers::{ETAG, IF_NONE_MATCH},
+    Body, Request, Response, StatusCode,
+};
 
-use std::{net::SocketAddr, path::PathBuf};
+use std::{
+    fs::Metadata,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// A dead simple http server for serving files at whatever address is specified by `--socket`
 #[derive(Clap, Debug)]
@@ -15,15 +28,30 @@ struct Opts {
     /// Specify the socket for the webserver to attempt to bind to
     #[clap(short, long, default_value = "127.0.0.1:9090")]
     socket: SocketAddr,
+    /// The address of the seeder to report on at `/health`
+    #[clap(long)]
+    seeder: Option<SocketAddr>,
+    /// The address of the tracker to report on at `/health`
+    #[clap(long)]
+    tracker: Option<SocketAddr>,
     /// The paths to the files that will be served at the root of the web server
     files: Vec<PathBuf>,
 }
 
+/// The other components that get checked on by `/health`
+#[derive(Clone, Debug, Default)]
+struct HealthChecks {
+    seeder: Option<SocketAddr>,
+    tracker: Option<SocketAddr>,
+}
+
 #[async_std::main]
 async fn main() -> tide::Result<()> {
     let Opts {
         socket,
         files,
+        seeder,
+        tracker,
         verbose,
         quiet,
     } = Opts::parse();
@@ -35,12 +63,203 @@ async fn main() -> tide::Result<()> {
     debug!("socket: {:?}", socket);
     debug!("files: {:#?}", files);
 
+    app(files, HealthChecks { seeder, tracker })
+        .listen(socket)
+        .await?;
+
+    Ok(())
+}
+
+fn app(files: Vec<PathBuf>, health_checks: HealthChecks) -> tide::Server<()> {
     let mut app = tide::new();
     for file in files {
-        let file_name = file.file_name().unwrap().to_string_lossy();
-        app.at(&format!("/{}", file_name)).serve_file(file)?;
+        let file_name = file.file_name().unwrap().to_string_lossy().into_owned();
+        // Files that don't exist yet, like the signature for an unsigned database, are a 404 until
+        // they show up
+        let file = file.canonicalize().unwrap_or(file);
+        app.at(&format!("/{}", file_name))
+            .get(move |req: Request<()>| {
+                let file = file.clone();
+                async move { serve_file(&file, &req).await }
+            });
     }
-    app.listen(socket).await?;
+    app.at("/health").get(move |_: Request<()>| {
+        let health_checks = health_checks.clone();
+        async move { health(&health_checks).await }
+    });
 
-    Ok(())
+    app
+}
+
+// Serves the file along with an `ETag` so that clients can skip downloading it again when it hasn't
+// changed
+async fn serve_file(path: &Path, req: &Request<()>) -> tide::Result {
+    let metadata = match fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            warn!("File not found: {:?}", path);
+            return Ok(Response::new(StatusCode::NotFound));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let etag = etag(&metadata);
+
+    let not_modified = req
+        .header(IF_NONE_MATCH)
+        .map(|values| {
+            values
+                .iter()
+                .any(|value| etag_matches(value.as_str(), &etag))
+        })
+        .unwrap_or(false);
+    if not_modified {
+        return Ok(Response::builder(StatusCode::NotModified)
+            .header(ETAG, etag)
+            .build());
+    }
+
+    let mut body = Body::from_file(path).await?;
+    body.set_mime(content_type(path));
+    Ok(Response::builder(StatusCode::Ok)
+        .header(ETAG, etag)
+        .body(body)
+        .build())
+}
+
+// Going off the size and modified time saves hashing the whole file on every request
+fn etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos())
+}
+
+// `If-None-Match` can list several tags and uses the weak comparison
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|tag| {
+        tag == "*"
+            || tag.strip_prefix("W/").unwrap_or(tag) == etag.strip_prefix("W/").unwrap_or(etag)
+    })
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("db") => "application/x-sqlite3",
+        _ => "application/octet-stream",
+    }
+}
+
+// Reports on each component as `true` or `false` depending on if it's up, or `null` when it isn't
+// being checked
+async fn health(health_checks: &HealthChecks) -> tide::Result {
+    let body = json!({
+        "seeder": is_up(health_checks.seeder).await,
+        "tracker": is_up(health_checks.tracker).await,
+    });
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&body)?)
+        .build())
+}
+
+async fn is_up(addr: Option<SocketAddr>) -> Option<bool> {
+    let addr = addr?;
+    Some(
+        io::timeout(HEALTH_CHECK_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .is_ok(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_std::task;
+    use tide::listener::Listener;
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    // Starts serving the app in the background, returning the url that it's served at
+    async fn spawn_app(app: tide::Server<()>) -> BoxResult<String> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let mut listener = app.bind(listener).await?;
+        task::spawn(async move { listener.accept().await });
+
+        Ok(url)
+    }
+
+    async fn get(url: String, if_none_match: Option<String>) -> BoxResult<ureq::Response> {
+        let response = task::spawn_blocking(move || {
+            let request = ureq::get(&url);
+            let request = match &if_none_match {
+                Some(etag) => request.set("If-None-Match", etag),
+                None => request,
+            };
+            request.call().map_err(Box::new)
+        })
+        .await?;
+
+        Ok(response)
+    }
+
+    #[async_std::test]
+    async fn cached_file() -> BoxResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_file = temp_dir.path().join("packages.db");
+        std::fs::write(&db_file, "packages")?;
+        let url = format!(
+            "{}/packages.db",
+            spawn_app(app(vec![db_file.clone()], HealthChecks::default())).await?
+        );
+
+        let response = get(url.clone(), None).await?;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), "application/x-sqlite3");
+        let etag = response.header("ETag").ok_or("Missing ETag")?.to_owned();
+        assert_eq!(response.into_string()?, "packages");
+
+        // A matching tag skips the body
+        let response = get(url.clone(), Some(etag.clone())).await?;
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.header("ETag"), Some(etag.as_str()));
+        assert_eq!(response.into_string()?, "");
+        let response = get(url.clone(), Some(format!("\"other\", W/{}", etag))).await?;
+        assert_eq!(response.status(), 304);
+
+        // While changing the file changes the tag
+        std::fs::write(&db_file, "new packages")?;
+        let response = get(url, Some(etag.clone())).await?;
+        assert_eq!(response.status(), 200);
+        assert_ne!(response.header("ETag"), Some(etag.as_str()));
+        assert_eq!(response.into_string()?, "new packages");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn health_check() -> BoxResult<()> {
+        // Something is listening for the tracker while the seeder's port was freed up
+        let tracker = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let seeder = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let health_checks = HealthChecks {
+            seeder: Some(seeder),
+            tracker: Some(tracker.local_addr()?),
+        };
+
+        let url = spawn_app(app(Vec::new(), health_checks)).await?;
+
+        let response = get(format!("{}/health", url), None).await?;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), "application/json");
+        assert_eq!(
+            response.into_string()?,
+            r#"{"seeder":false,"tracker":true}"#
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,143 @@
+use async_io::Timer;
+use futures_lite::future;
+
+use std::{
+    path::Path,
+    process::{Command, Output},
+    time::Duration,
+};
+
+use crate::{
+    command, entry::EntryChange, error::Error, find_listed_id, parse_list, validate_magnet,
+    Transmission,
+};
+
+// Async versions of the commands that can take a while to run. These mirror the blocking versions
+// so see those for more details
+impl Transmission {
+    pub async fn refresh_async(&mut self) -> Result<Vec<EntryChange>, Error> {
+        let output = self
+            .output_async(self.remote_command().arg("--list"))
+            .await
+            .and_then(command::check_remote)?;
+        let listing = parse_list(&String::from_utf8(output.stdout)?)?;
+
+        Ok(self.merge_listing(listing))
+    }
+
+    pub async fn download_torrent_async(&self, magnet: &str) -> Result<(), Error> {
+        validate_magnet(magnet)?;
+        self.output_async(&mut self.download_command(magnet))
+            .await
+            .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    pub async fn seed_local_torrent_async(&self, torrent_file: &Path) -> Result<u64, Error> {
+        self.output_async(&mut self.seed_command(torrent_file))
+            .await
+            .and_then(command::check_remote)?;
+
+        let output = self
+            .output_async(self.remote_command().arg("--list"))
+            .await
+            .and_then(command::check_remote)?;
+        find_listed_id(&String::from_utf8(output.stdout)?, torrent_file)
+    }
+
+    async fn output_async(&self, command: &mut Command) -> Result<Output, Error> {
+        output(command, self.opts.timeout).await
+    }
+}
+
+// Commands get built with the blocking `Command` so that they can be shared with the blocking API
+async fn output(command: &Command, timeout: Option<Duration>) -> Result<Output, Error> {
+    let mut async_command = async_process::Command::new(command.get_program());
+    async_command.args(command.get_args()).kill_on_drop(true);
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => async_command.env(key, value),
+            None => async_command.env_remove(key),
+        };
+    }
+    if let Some(dir) = command.get_current_dir() {
+        async_command.current_dir(dir);
+    }
+    let output = async {
+        async_command
+            .output()
+            .await
+            .map_err(|err| command::spawn_error(command, err))
+    };
+
+    match timeout {
+        Some(timeout) => {
+            let deadline = async {
+                Timer::after(timeout).await;
+                Err(Error::Timeout(timeout))
+            };
+            future::or(output, deadline).await
+        }
+        None => output.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::{
+        bytes::Bytes,
+        entry::{Entry, Status},
+        test_utils::FakeRemote,
+        TransmissionOpts,
+    };
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn refresh() -> BoxResult<()> {
+        // Stand in for `transmission-remote` by just spitting out the sample listing
+        let sample_file =
+            fs::canonicalize(Path::new("tests").join("corpus").join("entry_list.txt"))?;
+        let remote = FakeRemote::new(&format!("cat '{}'", sample_file.display()))?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+        future::block_on(transmission.refresh_async())?;
+
+        let name = "archlinux-2021.04.01-x86_64.iso";
+        let entry = Entry::completed(1, Bytes(786_800_000), Status::Seeding, name.to_owned())
+            .with_rates(Bytes(7_000), Bytes::zero())
+            .with_ratio(0.2);
+        assert_eq!(transmission.get_by_name(name), Some(&entry));
+
+        Ok(())
+    }
+
+    #[test]
+    fn timeout() {
+        let result = future::block_on(output(
+            Command::new("sleep").arg("5"),
+            Some(Duration::from_millis(100)),
+        ));
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+
+    #[test]
+    fn carries_over_env_and_dir() -> BoxResult<()> {
+        let dir = tempfile::tempdir()?;
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo \"$DISTPAC_VALUE\"; pwd")
+            .env("DISTPAC_VALUE", "kept")
+            .current_dir(dir.path());
+
+        let output = future::block_on(output(&command, None))?;
+        let expected = format!("kept\n{}\n", fs::canonicalize(dir.path())?.display());
+        assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+        Ok(())
+    }
+}
@@ -1,25 +1,93 @@
-use std::str::FromStr;
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub},
+    str::FromStr,
+};
 
 use crate::error::Error;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Bytes(pub f32);
+/// An amount of bytes
+///
+/// Stored as a whole number of bytes since an `f32` can't exactly represent sizes past ~16 MB
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bytes(pub u64);
 
 impl Bytes {
     pub fn zero() -> Self {
-        Self(0.0)
+        Self(0)
+    }
+
+    pub fn sum<I: Iterator<Item = Bytes>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl Add for Bytes {
+    type Output = Self;
+
+    // Saturates instead of overflowing so that summing up bogus sizes can't panic
+    fn add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign for Bytes {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Bytes {
+    type Output = Self;
+
+    // Saturates at zero since a negative amount of bytes doesn't make sense
+    fn sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
+impl From<u64> for Bytes {
+    fn from(amount: u64) -> Self {
+        Self(amount)
+    }
+}
+
+impl From<Bytes> for u64 {
+    fn from(bytes: Bytes) -> u64 {
+        bytes.0
     }
 }
 
 impl From<f32> for Bytes {
     fn from(amount: f32) -> Self {
-        Self(amount)
+        // Negative and NaN amounts saturate to zero
+        Self(amount.round() as u64)
     }
 }
 
 impl From<Bytes> for f32 {
     fn from(bytes: Bytes) -> f32 {
-        bytes.0
+        bytes.0 as f32
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Display with the largest unit that keeps the amount at or above 1
+        const UNITS: [(&str, u64); 4] = [
+            ("TB", 1_000_000_000_000),
+            ("GB", 1_000_000_000),
+            ("MB", 1_000_000),
+            ("kB", 1_000),
+        ];
+
+        for (unit, modifier) in UNITS.iter() {
+            if self.0 >= *modifier {
+                return write!(f, "{:.2} {}", self.0 as f64 / *modifier as f64, unit);
+            }
+        }
+
+        write!(f, "{} B", self.0)
     }
 }
 
@@ -29,20 +97,30 @@ impl FromStr for Bytes {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut pieces = s.split_whitespace();
 
-        let amount: f32 = pieces
+        let amount: f64 = pieces
             .next()
             .ok_or(Self::Err::InvalidByteFormat)?
             .parse()
             .map_err(|_| Self::Err::InvalidByteFormat)?;
         let modifier = match pieces.next().unwrap_or("B") {
-            "B" => 1e1,
+            "B" => 1e0,
             "kB" => 1e3,
             "MB" => 1e6,
             "GB" => 1e9,
+            "TB" => 1e12,
+            "PB" => 1e15,
+            "KiB" => 1024.0,
+            "MiB" => 1024.0 * 1024.0,
+            "GiB" => 1024.0 * 1024.0 * 1024.0,
+            "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
             _ => return Err(Self::Err::InvalidByteFormat),
         };
 
-        Ok(Self(amount * modifier))
+        if amount.is_sign_negative() || !amount.is_finite() {
+            return Err(Self::Err::InvalidByteFormat);
+        }
+
+        Ok(Self((amount * modifier).round() as u64))
     }
 }
 
@@ -53,8 +131,108 @@ mod tests {
     #[test]
     fn parsing() -> Result<(), Error> {
         let bytes: Bytes = "786.8 MB".parse()?;
-        assert_eq!(bytes, Bytes(786.8 * 1_000_000.0));
+        assert_eq!(bytes, Bytes(786_800_000));
+
+        let bytes: Bytes = "512 B".parse()?;
+        assert_eq!(bytes, Bytes(512));
+
+        let bytes: Bytes = "512".parse()?;
+        assert_eq!(bytes, Bytes(512));
+
+        let bytes: Bytes = "1.5 kB".parse()?;
+        assert_eq!(bytes, Bytes(1_500));
+
+        let bytes: Bytes = "2 GB".parse()?;
+        assert_eq!(bytes, Bytes(2_000_000_000));
+
+        let bytes: Bytes = "4.2 GB".parse()?;
+        assert_eq!(bytes, Bytes(4_200_000_000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_large_units() -> Result<(), Error> {
+        let bytes: Bytes = "2.5 TB".parse()?;
+        assert_eq!(bytes, Bytes(2_500_000_000_000));
+
+        let bytes: Bytes = "1 PB".parse()?;
+        assert_eq!(bytes, Bytes(1_000_000_000_000_000));
 
         Ok(())
     }
+
+    #[test]
+    fn parsing_binary_units() -> Result<(), Error> {
+        let bytes: Bytes = "1 KiB".parse()?;
+        assert_eq!(bytes, Bytes(1024));
+
+        let bytes: Bytes = "512.0 KiB".parse()?;
+        assert_eq!(bytes, Bytes(512 * 1024));
+
+        let bytes: Bytes = "1 GiB".parse()?;
+        assert_eq!(bytes, Bytes(1_073_741_824));
+
+        Ok(())
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Bytes::from(512.4_f32), Bytes(512));
+        assert_eq!(Bytes::from(-1.0_f32), Bytes::zero());
+        assert_eq!(f32::from(Bytes(512)), 512.0);
+        assert_eq!(u64::from(Bytes::from(4_200_000_000)), 4_200_000_000);
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(Bytes(1_000_000) + Bytes(2_000_000), Bytes(3_000_000));
+        assert_eq!(Bytes(3_000_000) - Bytes(1_000_000), Bytes(2_000_000));
+        assert_eq!(Bytes(1_000_000) - Bytes(2_000_000), Bytes::zero());
+        assert_eq!(Bytes(u64::MAX - 1) + Bytes(1), Bytes(u64::MAX));
+        assert_eq!(Bytes(u64::MAX) + Bytes(1), Bytes(u64::MAX));
+
+        let mut total = Bytes(1_000_000);
+        total += Bytes(2_000_000);
+        assert_eq!(total, Bytes(3_000_000));
+        total += Bytes(u64::MAX);
+        assert_eq!(total, Bytes(u64::MAX));
+
+        let sizes = vec![Bytes(1_000_000), Bytes(2_000_000), Bytes(3_000_000)];
+        assert_eq!(Bytes::sum(sizes.into_iter()), Bytes(6_000_000));
+        assert_eq!(Bytes::sum(std::iter::empty()), Bytes::zero());
+    }
+
+    #[test]
+    fn comparison() {
+        assert!(Bytes(100) < Bytes(200));
+        assert!(Bytes(200) >= Bytes(100));
+        assert!(Bytes(100) >= Bytes(100));
+        assert_eq!(Bytes(100).max(Bytes(200)), Bytes(200));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Bytes(512).to_string(), "512 B");
+        assert_eq!(Bytes(1_500).to_string(), "1.50 kB");
+        assert_eq!(Bytes(786_800_000).to_string(), "786.80 MB");
+        assert_eq!(Bytes(4_200_000_000).to_string(), "4.20 GB");
+        assert_eq!(Bytes(2_500_000_000_000).to_string(), "2.50 TB");
+    }
+
+    #[test]
+    fn parsing_invalid() {
+        assert!(matches!(
+            "5 QB".parse::<Bytes>(),
+            Err(Error::InvalidByteFormat)
+        ));
+        assert!(matches!(
+            "MB".parse::<Bytes>(),
+            Err(Error::InvalidByteFormat)
+        ));
+        assert!(matches!(
+            "-5 MB".parse::<Bytes>(),
+            Err(Error::InvalidByteFormat)
+        ));
+    }
 }
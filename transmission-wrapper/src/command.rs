@@ -0,0 +1,158 @@
+use std::{
+    io::{self, Read},
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::error::Error;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Runs the command to completion, killing it if it runs past the timeout
+pub(crate) fn output(command: &mut Command, timeout: Option<Duration>) -> Result<Output, Error> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return command.output().map_err(|err| spawn_error(command, err)),
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| spawn_error(command, err))?;
+
+    // The pipes have to be drained while waiting, otherwise a chatty command can block on a full
+    // pipe and look like it hung
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = match wait_with_deadline(&mut child, Instant::now() + timeout) {
+        Some(status) => status?,
+        None => return Err(Error::Timeout(timeout)),
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+// transmission-remote doesn't always exit with an error code when it fails, so also look for its
+// error messages in stderr
+pub(crate) fn check_remote(output: Output) -> Result<Output, Error> {
+    const ERROR_MARKERS: [&str; 2] = ["Unable to", "Couldn't"];
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reported_error = ERROR_MARKERS.iter().any(|marker| stderr.contains(marker));
+
+    if output.status.success() && !reported_error {
+        Ok(output)
+    } else if stderr.trim().is_empty() {
+        Err(Error::RemoteCommandFailed(format!(
+            "exited with {}",
+            output.status
+        )))
+    } else {
+        Err(Error::RemoteCommandFailed(stderr.trim().to_owned()))
+    }
+}
+
+// Points out which binary is missing instead of just "No such file or directory"
+pub(crate) fn spawn_error(command: &Command, err: io::Error) -> Error {
+    if err.kind() == io::ErrorKind::NotFound {
+        Error::BinaryNotFound(command.get_program().to_string_lossy().into_owned())
+    } else {
+        Error::CommandFailed(err)
+    }
+}
+
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+// Returns `None` if the child had to be killed for running past the deadline
+fn wait_with_deadline(child: &mut Child, deadline: Instant) -> Option<io::Result<ExitStatus>> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(Ok(status)),
+            Ok(None) => {}
+            Err(err) => return Some(Err(err)),
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout() {
+        let start = Instant::now();
+        let result = output(
+            Command::new("sleep").arg("5"),
+            Some(Duration::from_millis(100)),
+        );
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn missing_binary() {
+        for timeout in [None, Some(Duration::from_secs(5))].iter() {
+            match output(&mut Command::new("distpac-missing-binary"), *timeout) {
+                Err(Error::BinaryNotFound(name)) => assert_eq!(name, "distpac-missing-binary"),
+                other => panic!("Expected a missing binary error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn remote_errors() -> Result<(), Error> {
+        let run = |script: &str| output(Command::new("sh").arg("-c").arg(script), None);
+
+        let message = "transmission-remote: (http://localhost:9091/transmission/rpc/) Couldn't \
+            connect to server";
+        match check_remote(run(&format!("echo \"{}\" >&2", message))?) {
+            Err(Error::RemoteCommandFailed(stderr)) => assert_eq!(stderr, message),
+            other => panic!("Expected the remote to fail, got {:?}", other),
+        }
+        assert!(matches!(
+            check_remote(run("exit 1")?),
+            Err(Error::RemoteCommandFailed(_))
+        ));
+
+        // Other noise on stderr is fine as long as the command succeeded
+        check_remote(run("echo 'some warning' >&2")?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn finishes_in_time() -> Result<(), Error> {
+        let output = output(
+            Command::new("echo").arg("hello"),
+            Some(Duration::from_secs(5)),
+        )?;
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello\n");
+
+        Ok(())
+    }
+}
@@ -1,2 +1,4 @@
 pub const DAEMON_NAME: &str = "transmission-daemon";
 pub const REMOTE_NAME: &str = "transmission-remote";
+pub const DEFAULT_HOST: &str = "localhost";
+pub const DEFAULT_RPC_PORT: u16 = 9091;
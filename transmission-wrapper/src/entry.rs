@@ -1,10 +1,10 @@
 use getset::{Getters, Setters};
 
-use std::{process::Command, str::FromStr};
+use std::{str::FromStr, time::Duration};
 
-use crate::{bytes::Bytes, constants::REMOTE_NAME, error::Error};
+use crate::{bytes::Bytes, error::Error};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Status {
     Downloading,
     Idle,
@@ -12,6 +12,14 @@ pub enum Status {
     Stopped,
     UpAndDown,
     Verifying,
+    /// Waiting on a free download or seed slot
+    Queued,
+    /// Waiting to be verified
+    Checking,
+    /// Stopped after reaching the seed ratio or idle limit
+    Finished,
+    /// Anything this version of the wrapper doesn't know about
+    Unknown(String),
 }
 
 impl FromStr for Status {
@@ -25,51 +33,106 @@ impl FromStr for Status {
             "Stopped" => Ok(Self::Stopped),
             "Up & Down" => Ok(Self::UpAndDown),
             "Verifying" => Ok(Self::Verifying),
-            _ => Err(Self::Err::InvalidEntryFormat),
+            "Queued" => Ok(Self::Queued),
+            "Will Verify" | "Checking" => Ok(Self::Checking),
+            "Finished" => Ok(Self::Finished),
+            unknown => Ok(Self::Unknown(unknown.to_owned())),
         }
     }
 }
 
+/// A change to the tracked entries from a refresh, referenced by torrent id
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryChange {
+    Added(u64),
+    Removed(u64),
+    Updated(u64),
+}
+
 #[derive(Getters, Setters, Clone, Debug, PartialEq)]
 #[getset(get = "pub")]
 pub struct Entry {
     id: u64,
-    size: Bytes,
+    /// `None` until transmission knows the torrent's metadata
+    size: Option<Bytes>,
     downloaded: Bytes,
     status: Status,
     name: String,
+    up_rate: Bytes,
+    down_rate: Bytes,
+    ratio: f32,
+    eta: Option<Duration>,
 }
 
 impl Entry {
-    pub fn from_id(id: u64) -> Result<Self, Error> {
-        let output = Command::new(REMOTE_NAME)
-            .arg("--torrent")
-            .arg(id.to_string())
-            .arg("--info")
-            .output()?;
-
-        String::from_utf8(output.stdout)?.parse()
+    pub fn completed(id: u64, downloaded: Bytes, status: Status, name: String) -> Self {
+        Self::new(id, Some(downloaded), downloaded, status, name)
     }
 
-    pub fn completed(id: u64, downloaded: Bytes, status: Status, name: String) -> Self {
-        Self::new(id, downloaded, downloaded, status, name)
+    pub fn is_paused(&self) -> bool {
+        self.status == Status::Stopped
     }
 
+    /// Everything has been downloaded, regardless of whether it's still seeding. An unknown size
+    /// is never finished
     pub fn is_finished(&self) -> bool {
-        self.size != Bytes(0.0) && self.size == self.downloaded
+        matches!(self.size, Some(size) if size != Bytes::zero() && self.downloaded >= size)
+    }
+
+    /// How much has been downloaded from `0.0` to `1.0`, which stays at `0.0` while the size is
+    /// unknown
+    pub fn progress_fraction(&self) -> f32 {
+        match self.size {
+            Some(size) if size != Bytes::zero() => {
+                (self.downloaded.0 as f64 / size.0 as f64).min(1.0) as f32
+            }
+            _ => 0.0,
+        }
     }
 
-    pub fn new(id: u64, size: Bytes, downloaded: Bytes, status: Status, name: String) -> Self {
+    pub fn new(
+        id: u64,
+        size: Option<Bytes>,
+        downloaded: Bytes,
+        status: Status,
+        name: String,
+    ) -> Self {
         Self {
             id,
             size,
             downloaded,
             status,
             name,
+            up_rate: Bytes::zero(),
+            down_rate: Bytes::zero(),
+            ratio: 0.0,
+            eta: None,
         }
     }
 
-    pub fn update(&mut self, downloaded: Bytes, status: Status) {
+    /// Sets the upload and download rates in bytes per second
+    pub fn with_rates(mut self, up_rate: Bytes, down_rate: Bytes) -> Self {
+        self.up_rate = up_rate;
+        self.down_rate = down_rate;
+        self
+    }
+
+    pub fn with_ratio(mut self, ratio: f32) -> Self {
+        self.ratio = ratio;
+        self
+    }
+
+    pub fn with_eta(mut self, eta: Option<Duration>) -> Self {
+        self.eta = eta;
+        self
+    }
+
+    /// An unknown `size` leaves the current size alone, so a size that's already known never gets
+    /// lost
+    pub fn update(&mut self, size: Option<Bytes>, downloaded: Bytes, status: Status) {
+        if size.is_some() {
+            self.size = size;
+        }
         self.downloaded = downloaded;
         self.status = status;
     }
@@ -81,6 +144,8 @@ impl FromStr for Entry {
     // Parses and entry from the `transmission-remote --torrent <torrent id> --info` output
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut info = Vec::new();
+        // The ETA isn't always listed so it's tracked separately
+        let mut eta = None;
 
         // Go over each line of the output picking out special info. A trimmed version is below
         // NAME
@@ -89,8 +154,12 @@ impl FromStr for Entry {
         //
         // TRANSFER
         //     State: <torrent status>
+        //     ETA: <time remaining> (<seconds remaining>)
+        //     Download Speed: <download rate>
+        //     Upload Speed: <upload rate>
         //     Have: <downloaded> (unwanted junk)
         //     Total size: <torrent size> (unwanted junk)
+        //     Ratio: <share ratio>
         for line in s.lines() {
             let line = line.trim();
 
@@ -122,30 +191,92 @@ impl FromStr for Entry {
                 info.push(status);
             } else if let Some(name) = line.strip_prefix("Name: ") {
                 info.push(name);
+            } else if let Some(down_rate) = line.strip_prefix("Download Speed: ") {
+                info.push(down_rate);
+            } else if let Some(up_rate) = line.strip_prefix("Upload Speed: ") {
+                info.push(up_rate);
+            } else if let Some(ratio) = line.strip_prefix("Ratio: ") {
+                info.push(ratio);
+            } else if let Some(eta_str) = line.strip_prefix("ETA: ") {
+                eta = parse_eta(eta_str)?;
             }
         }
 
         match info.as_slice() {
-            [id_str, name_str, status, downloaded_str, size_str] => Ok(Self {
-                id: id_str.parse().map_err(|_| Self::Err::InvalidEntryFormat)?,
-                size: if *size_str == "None" {
-                    Bytes(0.0)
-                } else {
-                    size_str.parse()?
-                },
-                downloaded: if *downloaded_str == "None" {
-                    Bytes(0.0)
-                } else {
-                    downloaded_str.parse()?
-                },
-                status: status.parse()?,
-                name: name_str.to_string(),
-            }),
+            [id_str, name_str, status, down_rate_str, up_rate_str, downloaded_str, size_str, ratio_str] => {
+                Ok(Self {
+                    id: id_str.parse().map_err(|_| Self::Err::InvalidEntryFormat)?,
+                    size: if *size_str == "None" {
+                        None
+                    } else {
+                        Some(size_str.parse()?)
+                    },
+                    downloaded: if *downloaded_str == "None" {
+                        Bytes::zero()
+                    } else {
+                        downloaded_str.parse()?
+                    },
+                    status: status.parse()?,
+                    name: name_str.to_string(),
+                    up_rate: parse_rate(up_rate_str)?,
+                    down_rate: parse_rate(down_rate_str)?,
+                    ratio: parse_ratio(ratio_str)?,
+                    eta,
+                })
+            }
             _ => Err(Self::Err::InvalidEntryFormat),
         }
     }
 }
 
+// Rates are displayed as kB/s, but the `--list` output leaves off the unit entirely
+pub(crate) fn parse_rate(s: &str) -> Result<Bytes, Error> {
+    let rate = s.trim_end_matches("/s");
+    if rate.contains(' ') {
+        rate.parse()
+    } else {
+        format!("{} kB", rate).parse()
+    }
+}
+
+// The ratio is displayed as a placeholder until there's something to compare against
+pub(crate) fn parse_ratio(s: &str) -> Result<f32, Error> {
+    match s {
+        "None" | "n/a" => Ok(0.0),
+        ratio => ratio.parse().map_err(|_| Error::InvalidEntryFormat),
+    }
+}
+
+// Parses the ETA as displayed by either `--list` (e.g. "5 min") or `--info` (e.g. "5 minutes
+// (300 seconds)"). Finished and stalled torrents have no ETA
+pub(crate) fn parse_eta(s: &str) -> Result<Option<Duration>, Error> {
+    // Prefer the exact seconds from `--info` when they're present
+    let s = match (s.find('('), s.rfind(')')) {
+        (Some(start), Some(end)) if start < end => &s[start + 1..end],
+        _ => s,
+    };
+
+    if s == "Done" || s == "Unknown" {
+        return Ok(None);
+    }
+
+    let mut pieces = s.split_whitespace();
+    let amount: u64 = pieces
+        .next()
+        .ok_or(Error::InvalidEntryFormat)?
+        .parse()
+        .map_err(|_| Error::InvalidEntryFormat)?;
+    let modifier = match pieces.next().ok_or(Error::InvalidEntryFormat)? {
+        "sec" | "secs" | "second" | "seconds" => 1,
+        "min" | "mins" | "minute" | "minutes" => 60,
+        "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "day" | "days" => 60 * 60 * 24,
+        _ => return Err(Error::InvalidEntryFormat),
+    };
+
+    Ok(Some(Duration::from_secs(amount * modifier)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,7 +291,7 @@ mod tests {
         let torrent_info = fs::read_to_string(&sample_file)?;
         let entry: Entry = torrent_info.parse()?;
 
-        let size = Bytes::from(786.8 * 1_000_000.0);
+        let size = Bytes(786_800_000);
         assert_eq!(
             entry,
             Entry::completed(
@@ -169,7 +300,140 @@ mod tests {
                 Status::Idle,
                 "archlinux-2021.04.01-x86_64.iso".to_owned()
             )
+            .with_ratio(0.2)
+            .with_eta(Some(Duration::from_secs(0)))
         );
+        assert_eq!(entry.up_rate(), &Bytes::zero());
+        assert_eq!(entry.down_rate(), &Bytes::zero());
+        assert_eq!(entry.ratio(), &0.2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_statuses() -> Result<(), Error> {
+        assert_eq!("Downloading".parse::<Status>()?, Status::Downloading);
+        assert_eq!("Idle".parse::<Status>()?, Status::Idle);
+        assert_eq!("Seeding".parse::<Status>()?, Status::Seeding);
+        assert_eq!("Stopped".parse::<Status>()?, Status::Stopped);
+        assert_eq!("Up & Down".parse::<Status>()?, Status::UpAndDown);
+        assert_eq!("Verifying".parse::<Status>()?, Status::Verifying);
+        assert_eq!("Queued".parse::<Status>()?, Status::Queued);
+        assert_eq!("Will Verify".parse::<Status>()?, Status::Checking);
+        assert_eq!("Checking".parse::<Status>()?, Status::Checking);
+        assert_eq!("Finished".parse::<Status>()?, Status::Finished);
+        assert_eq!(
+            "Magnetizing".parse::<Status>()?,
+            Status::Unknown("Magnetizing".to_owned())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn paused() {
+        let entry = Entry::new(
+            1,
+            Some(Bytes(100)),
+            Bytes(50),
+            Status::Stopped,
+            "paused".to_owned(),
+        );
+        assert!(entry.is_paused());
+
+        let entry = Entry::new(
+            1,
+            Some(Bytes(100)),
+            Bytes(50),
+            Status::Downloading,
+            "active".to_owned(),
+        );
+        assert!(!entry.is_paused());
+    }
+
+    #[test]
+    fn progress() {
+        let entry = |size, downloaded| {
+            Entry::new(
+                1,
+                size,
+                Bytes(downloaded),
+                Status::Downloading,
+                "progress".to_owned(),
+            )
+        };
+
+        let partial = entry(Some(Bytes(200)), 50);
+        assert!(!partial.is_finished());
+        assert_eq!(partial.progress_fraction(), 0.25);
+
+        let complete = Entry::completed(1, Bytes(200), Status::Seeding, "progress".to_owned());
+        assert!(complete.is_finished());
+        assert_eq!(complete.progress_fraction(), 1.0);
+
+        // Clamped even if more shows up as downloaded than the size
+        assert_eq!(entry(Some(Bytes(200)), 300).progress_fraction(), 1.0);
+        let unknown = entry(None, 0);
+        assert!(!unknown.is_finished());
+        assert_eq!(unknown.progress_fraction(), 0.0);
+    }
+
+    #[test]
+    fn size_becomes_known() {
+        let mut entry = Entry::new(
+            1,
+            None,
+            Bytes::zero(),
+            Status::Downloading,
+            "magnet".to_owned(),
+        );
+        assert_eq!(entry.size(), &None);
+
+        entry.update(Some(Bytes(200)), Bytes(50), Status::Downloading);
+        assert_eq!(entry.size(), &Some(Bytes(200)));
+        assert_eq!(entry.progress_fraction(), 0.25);
+
+        // A later unknown size doesn't throw away the real one
+        entry.update(None, Bytes(200), Status::Seeding);
+        assert_eq!(entry.size(), &Some(Bytes(200)));
+        assert!(entry.is_finished());
+    }
+
+    #[test]
+    fn parse_etas() -> Result<(), Error> {
+        assert_eq!(parse_eta("5 min")?, Some(Duration::from_secs(5 * 60)));
+        assert_eq!(parse_eta("1 hrs")?, Some(Duration::from_secs(60 * 60)));
+        assert_eq!(
+            parse_eta("2 days")?,
+            Some(Duration::from_secs(2 * 60 * 60 * 24))
+        );
+        assert_eq!(
+            parse_eta("1 hour, 5 minutes (3900 seconds)")?,
+            Some(Duration::from_secs(3900))
+        );
+        assert_eq!(parse_eta("Done")?, None);
+        assert_eq!(parse_eta("Unknown")?, None);
+        assert!(parse_eta("soon").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_ratios() -> Result<(), Error> {
+        assert_eq!(parse_ratio("1.5")?, 1.5);
+        assert_eq!(parse_ratio("None")?, 0.0);
+        assert_eq!(parse_ratio("n/a")?, 0.0);
+        assert!(parse_ratio("lots").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rates() -> Result<(), Error> {
+        assert_eq!(parse_rate("7.0")?, Bytes(7_000));
+        assert_eq!(parse_rate("0.0")?, Bytes::zero());
+        assert_eq!(parse_rate("12 kB/s")?, Bytes(12_000));
+        assert_eq!(parse_rate("1.5 MB/s")?, Bytes(1_500_000));
 
         Ok(())
     }
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use std::{io, string::FromUtf8Error};
+use std::{io, path::PathBuf, string::FromUtf8Error, time::Duration};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -8,8 +8,24 @@ pub enum Error {
     InvalidByteFormat,
     #[error("An entry string was in an unrecognized format")]
     InvalidEntryFormat,
+    #[error("A torrent's file list was in an unrecognized format")]
+    InvalidFileFormat,
     #[error("A transmission command failed to execute Error: {0}")]
     CommandFailed(#[from] io::Error),
     #[error("A command return invalid UTF-8 Error: {0}")]
     InvalidUTF8(#[from] FromUtf8Error),
+    #[error("Another transmission handle is already active")]
+    AlreadyRunning,
+    #[error("A transmission command took longer than {0:?} to finish")]
+    Timeout(Duration),
+    #[error("Couldn't find `{0}`. Make sure that transmission is installed and on your PATH")]
+    BinaryNotFound(String),
+    #[error("No torrent with id {0}")]
+    MissingTorrent(u64),
+    #[error("Invalid magnet link {0}")]
+    InvalidMagnet(String),
+    #[error("Couldn't find the torrent added from {0}")]
+    MissingAddedTorrent(PathBuf),
+    #[error("transmission-remote reported an error: {0}")]
+    RemoteCommandFailed(String),
 }
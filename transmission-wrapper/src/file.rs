@@ -0,0 +1,127 @@
+use getset::Getters;
+
+use std::path::PathBuf;
+
+use crate::{bytes::Bytes, error::Error};
+
+/// A single file contained in a torrent
+#[derive(Getters, Clone, Debug, PartialEq)]
+#[getset(get = "pub")]
+pub struct TorrentFile {
+    /// Relative to the torrent's download dir, so it starts with the torrent's name for torrents
+    /// with multiple files
+    path: PathBuf,
+    size: Bytes,
+    /// How much of the file has been downloaded from `0.0` to `1.0`
+    progress: f32,
+    /// Whether the file is going to be downloaded at all
+    wanted: bool,
+}
+
+impl TorrentFile {
+    pub fn new(path: PathBuf, size: Bytes, progress: f32, wanted: bool) -> Self {
+        Self {
+            path,
+            size,
+            progress,
+            wanted,
+        }
+    }
+}
+
+// Parses the `transmission-remote --torrent <torrent id> --files` output which looks like
+// <torrent name> (<file count> files):
+//   #  Done Priority Get      Size  Name
+//   0: 100% Normal   Yes   1.20 kB  <file path>
+pub(crate) fn parse_files(s: &str) -> Result<Vec<TorrentFile>, Error> {
+    s.lines()
+        .skip(2)
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_file)
+        .collect()
+}
+
+fn parse_file(line: &str) -> Result<TorrentFile, Error> {
+    let (_index, rest) = line.split_once(':').ok_or(Error::InvalidFileFormat)?;
+    let (done, rest) = next_word(rest)?;
+    let (_priority, rest) = next_word(rest)?;
+    let (get, rest) = next_word(rest)?;
+    // The size is either "None" or an amount followed by a unit
+    let (amount, rest) = next_word(rest)?;
+    let (size, path) = if amount == "None" {
+        (Bytes::zero(), rest)
+    } else {
+        let (unit, rest) = next_word(rest)?;
+        (format!("{} {}", amount, unit).parse()?, rest)
+    };
+
+    let progress: f32 = done
+        .strip_suffix('%')
+        .and_then(|percentage| percentage.parse().ok())
+        .ok_or(Error::InvalidFileFormat)?;
+    let wanted = match get {
+        "Yes" => true,
+        "No" => false,
+        _ => return Err(Error::InvalidFileFormat),
+    };
+    if path.is_empty() {
+        return Err(Error::InvalidFileFormat);
+    }
+
+    Ok(TorrentFile::new(
+        PathBuf::from(path),
+        size,
+        progress / 100.0,
+        wanted,
+    ))
+}
+
+// Splits off the next whitespace separated word, leaving the rest without any leading whitespace
+fn next_word(s: &str) -> Result<(&str, &str), Error> {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(end) => Ok((&s[..end], s[end..].trim_start())),
+        None if !s.is_empty() => Ok((s, "")),
+        None => Err(Error::InvalidFileFormat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, path::Path};
+
+    type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn parse_file_list() -> BoxResult<()> {
+        let sample_file = Path::new("tests").join("corpus").join("torrent_files.txt");
+        let files = parse_files(&fs::read_to_string(sample_file)?)?;
+
+        let root = Path::new("ripgrep-12.1.1");
+        assert_eq!(
+            files,
+            [
+                TorrentFile::new(root.join("assets").join("rg"), Bytes(15), 1.0, true),
+                TorrentFile::new(root.join("manifest.yaml"), Bytes(1_200), 0.5, true),
+                TorrentFile::new(
+                    root.join("scripts").join("install.sh"),
+                    Bytes::zero(),
+                    0.0,
+                    true
+                ),
+                TorrentFile::new(
+                    root.join("docs").join("user guide.pdf"),
+                    Bytes(2_500_000),
+                    0.0,
+                    false
+                ),
+            ]
+        );
+
+        assert!(parse_files("name (1 files):\n  #  Done\n  0: lots").is_err());
+
+        Ok(())
+    }
+}
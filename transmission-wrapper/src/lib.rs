@@ -1,27 +1,48 @@
-use dist_utils::misc::stop_process_by_name;
+use dist_utils::misc::{stop_process_by_name, Stopped, DEFAULT_GRACE_PERIOD};
+use getset::Getters;
+use log::warn;
 use sysinfo::{System, SystemExt};
 
 use std::{
-    io,
+    net::{TcpStream, ToSocketAddrs},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Command, Output},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
 };
 
 use crate::{
     bytes::Bytes,
-    constants::{DAEMON_NAME, REMOTE_NAME},
-    entry::Entry,
+    constants::{DAEMON_NAME, DEFAULT_HOST, DEFAULT_RPC_PORT, REMOTE_NAME},
+    entry::{parse_eta, parse_rate, parse_ratio, Entry, EntryChange},
     error::Error,
+    file::{parse_files, TorrentFile},
 };
 
+#[cfg(feature = "async")]
+mod asynchronous;
 pub mod bytes;
+mod command;
 mod constants;
 pub mod entry;
 pub mod error;
+pub mod file;
+#[cfg(test)]
+mod test_utils;
 
 #[derive(Default, Debug)]
 pub struct TransmissionOpts {
     pub download_dir: Option<PathBuf>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub timeout: Option<Duration>,
+    /// Where a newly started daemon writes its process id
+    pub pid_file: Option<PathBuf>,
+    /// Labels given to every torrent that gets added
+    pub labels: Vec<String>,
 }
 
 impl TransmissionOpts {
@@ -33,46 +54,171 @@ impl TransmissionOpts {
         self.download_dir = Some(download_dir);
         self
     }
+
+    pub fn username(mut self, username: String) -> Self {
+        self.username = Some(username);
+        self
+    }
+
+    pub fn password(mut self, password: String) -> Self {
+        self.password = Some(password);
+        self
+    }
+
+    pub fn host(mut self, host: String) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn pid_file(mut self, pid_file: PathBuf) -> Self {
+        self.pid_file = Some(pid_file);
+        self
+    }
+
+    pub fn labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    // The RPC endpoint if it's something other than the default local daemon
+    fn rpc_endpoint(&self) -> Option<String> {
+        if self.host.is_none() && self.port.is_none() {
+            return None;
+        }
+
+        Some(format!(
+            "{}:{}",
+            self.host.as_deref().unwrap_or(DEFAULT_HOST),
+            self.port.unwrap_or(DEFAULT_RPC_PORT)
+        ))
+    }
+}
+
+// Held by the live `Transmission` handle so that only one can be created at a time
+static HANDLE_LOCK: AtomicBool = AtomicBool::new(false);
+
+/// The totals from the `Sum:` line of `transmission-remote --list`
+#[derive(Getters, Clone, Copy, Debug, Default, PartialEq)]
+#[getset(get = "pub")]
+pub struct TransmissionSummary {
+    downloaded: Bytes,
+    up_rate: Bytes,
+    down_rate: Bytes,
 }
 
-// TODO: ideally there should be a global lock so that only one of these can be created at a time
 pub struct Transmission {
     entries: Vec<Entry>,
-    download_dir: Option<PathBuf>,
+    summary: TransmissionSummary,
+    opts: TransmissionOpts,
+    holds_lock: bool,
+    // Only ever changed to point at a stand-in for tests
+    remote_name: String,
+}
+
+struct Listing {
+    entries: Vec<Entry>,
+    summary: TransmissionSummary,
 }
 
 impl Transmission {
     fn empty(opts: TransmissionOpts) -> Self {
         Self {
             entries: Vec::new(),
-            download_dir: opts.download_dir,
+            summary: TransmissionSummary::default(),
+            opts,
+            holds_lock: false,
+            remote_name: REMOTE_NAME.to_owned(),
         }
     }
 
+    fn locked(opts: TransmissionOpts) -> Result<Self, Error> {
+        HANDLE_LOCK
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .map_err(|_| Error::AlreadyRunning)?;
+
+        let mut transmission = Self::empty(opts);
+        transmission.holds_lock = true;
+        Ok(transmission)
+    }
+
     pub fn start(opts: TransmissionOpts) -> Result<Self, Error> {
-        // Start the daemon if it's not already running
-        if !Self::is_running() {
+        let transmission = Self::locked(opts)?;
+
+        // Start the daemon if it's not already running. A daemon on a remote host can't be started
+        // from here though
+        if !Self::is_running(&transmission.opts) && transmission.opts.host.is_none() {
             let mut command = Command::new(DAEMON_NAME);
 
-            if let Some(download_dir) = &opts.download_dir {
-                command.arg("--download-dir").arg(&download_dir);
+            if let Some(download_dir) = &transmission.opts.download_dir {
+                command.arg("--download-dir").arg(download_dir);
+            }
+
+            if let Some(port) = transmission.opts.port {
+                command.arg("--port").arg(port.to_string());
+            }
+
+            if let Some(pid_file) = &transmission.opts.pid_file {
+                command.arg("--pid-file").arg(pid_file);
             }
 
-            command.spawn()?;
+            command
+                .spawn()
+                .map_err(|err| command::spawn_error(&command, err))?;
         }
 
-        Ok(Self::empty(opts))
+        Ok(transmission)
     }
 
-    pub fn from_running(opts: TransmissionOpts) -> Option<Self> {
-        Self::is_running().then(|| Self::empty(opts))
+    pub fn from_running(opts: TransmissionOpts) -> Result<Option<Self>, Error> {
+        // Checking for the process first is a lot cheaper than going through the remote
+        if Self::is_running(&opts) {
+            Ok(Self::locked(opts)?.answering())
+        } else {
+            Ok(None)
+        }
+    }
+
+    // A daemon that's still starting up or is wedged won't answer over RPC
+    fn answering(self) -> Option<Self> {
+        // `transmission-remote --session-info`
+        let answered = self
+            .output(self.remote_command().arg("--session-info"))
+            .and_then(command::check_remote)
+            .is_ok();
+
+        if answered {
+            Some(self)
+        } else {
+            None
+        }
     }
 
-    pub fn stop(self) {
-        stop_process_by_name(DAEMON_NAME);
+    /// Gives the daemon a chance to save its state before it gets killed
+    pub fn stop(self) -> Stopped {
+        stop_process_by_name(DAEMON_NAME, DEFAULT_GRACE_PERIOD)
     }
 
-    fn is_running() -> bool {
+    fn is_running(opts: &TransmissionOpts) -> bool {
+        // A custom endpoint has to actually be reachable
+        if let Some(endpoint) = opts.rpc_endpoint() {
+            return endpoint
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .map(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok())
+                .unwrap_or(false);
+        }
+
         let mut system = System::new();
         system.refresh_all();
         let processes = system.get_process_by_name(DAEMON_NAME);
@@ -80,10 +226,35 @@ impl Transmission {
         !processes.is_empty()
     }
 
-    pub fn seed_local_torrent(&self, torrent_file: &Path) -> io::Result<()> {
+    /// Returns the id that transmission assigned to the torrent
+    pub fn seed_local_torrent(&self, torrent_file: &Path) -> Result<u64, Error> {
+        self.output(&mut self.seed_command(torrent_file))
+            .and_then(command::check_remote)?;
+
+        // The `--add` response doesn't include the id, so it gets looked up in the listing instead
+        let output = self
+            .output(self.remote_command().arg("--list"))
+            .and_then(command::check_remote)?;
+        find_listed_id(&String::from_utf8(output.stdout)?, torrent_file)
+    }
+
+    pub fn download_torrent(&self, magnet: &str) -> Result<(), Error> {
+        validate_magnet(magnet)?;
+        self.output(&mut self.download_command(magnet))
+            .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    pub fn download_torrent_file(&self, torrent_file: &Path) -> Result<(), Error> {
+        self.output(&mut self.download_file_command(torrent_file))
+            .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    fn seed_command(&self, torrent_file: &Path) -> Command {
         // `transmission-remote --torrent torrent_path --add torrent_path \
         // --verify --start --download-dir download_dir`
-        let mut command = Command::new(REMOTE_NAME);
+        let mut command = self.remote_command();
         command
             .arg("--torrent")
             .arg(torrent_file)
@@ -92,106 +263,488 @@ impl Transmission {
             .arg("--verify")
             .arg("--start");
 
-        if let Some(download_dir) = &self.download_dir {
-            command.arg("--download-dir").arg(download_dir);
-        }
+        self.add_options(&mut command);
+
+        command
+    }
+
+    fn download_file_command(&self, torrent_file: &Path) -> Command {
+        // `transmission-remote --add torrent_path --start --download-dir download_dir`
+        let mut command = self.remote_command();
+        command.arg("--add").arg(torrent_file).arg("--start");
+        self.add_options(&mut command);
 
         command
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()?;
-        Ok(())
     }
 
-    pub fn download_torrent(&self, magnet: &str) -> io::Result<()> {
+    fn download_command(&self, magnet: &str) -> Command {
         // `transmission-remote --add magnet_link --download-dir download_dir`
-        let mut command = Command::new(REMOTE_NAME);
+        let mut command = self.remote_command();
         command.arg("--add").arg(magnet);
+        self.add_options(&mut command);
 
-        if let Some(download_dir) = &self.download_dir {
+        command
+    }
+
+    // The options that apply to any torrent being added, e.g.
+    // `--download-dir download_dir --labels label1,label2`
+    fn add_options(&self, command: &mut Command) {
+        if let Some(download_dir) = &self.opts.download_dir {
             command.arg("--download-dir").arg(download_dir);
         }
 
-        command
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()?;
+        if !self.opts.labels.is_empty() {
+            command.arg("--labels").arg(self.opts.labels.join(","));
+        }
+    }
+
+    pub fn remove_torrent(&mut self, id: u64) -> Result<(), Error> {
+        // `transmission-remote --torrent id --remove`
+        self.remove(id, "--remove")
+    }
+
+    pub fn remove_torrent_with_data(&mut self, id: u64) -> Result<(), Error> {
+        // `transmission-remote --torrent id --remove-and-delete`
+        self.remove(id, "--remove-and-delete")
+    }
+
+    fn remove(&mut self, id: u64, remove_flag: &str) -> Result<(), Error> {
+        if self.get_mut_by_id(id).is_none() {
+            return Err(Error::MissingTorrent(id));
+        }
+
+        self.torrent_action(&id.to_string(), remove_flag)?;
+        self.remove_entry(id);
+        Ok(())
+    }
+
+    pub fn pause_torrent(&self, id: u64) -> Result<(), Error> {
+        // `transmission-remote --torrent id --stop`
+        self.torrent_action(&id.to_string(), "--stop")
+    }
+
+    pub fn resume_torrent(&self, id: u64) -> Result<(), Error> {
+        // `transmission-remote --torrent id --start`
+        self.torrent_action(&id.to_string(), "--start")
+    }
+
+    /// Re-checks the downloaded data, so that any bad pieces get downloaded again
+    pub fn verify_torrent(&self, id: u64) -> Result<(), Error> {
+        // `transmission-remote --torrent id --verify`
+        self.torrent_action(&id.to_string(), "--verify")
+    }
+
+    /// Stops seeding the torrent once it reaches `ratio`
+    pub fn set_seed_ratio(&self, id: u64, ratio: f32) -> Result<(), Error> {
+        // `transmission-remote --torrent id --seedratio ratio`
+        self.output(
+            self.remote_command()
+                .arg("--torrent")
+                .arg(id.to_string())
+                .arg("--seedratio")
+                .arg(ratio.to_string()),
+        )
+        .and_then(command::check_remote)?;
         Ok(())
     }
 
+    /// Lists the files contained in the torrent along with how much of each has been downloaded
+    pub fn torrent_files(&self, id: u64) -> Result<Vec<TorrentFile>, Error> {
+        // `transmission-remote --torrent id --files`
+        let output = self
+            .output(
+                self.remote_command()
+                    .arg("--torrent")
+                    .arg(id.to_string())
+                    .arg("--files"),
+            )
+            .and_then(command::check_remote)?;
+
+        parse_files(&String::from_utf8(output.stdout)?)
+    }
+
+    pub fn pause_all(&self) -> Result<(), Error> {
+        // `transmission-remote --torrent all --stop`
+        self.torrent_action("all", "--stop")
+    }
+
+    pub fn resume_all(&self) -> Result<(), Error> {
+        // `transmission-remote --torrent all --start`
+        self.torrent_action("all", "--start")
+    }
+
+    pub fn set_download_limit(&self, kbps: u32) -> Result<(), Error> {
+        // `transmission-remote --downlimit kbps`
+        self.output(
+            self.remote_command()
+                .arg("--downlimit")
+                .arg(kbps.to_string()),
+        )
+        .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    pub fn set_upload_limit(&self, kbps: u32) -> Result<(), Error> {
+        // `transmission-remote --uplimit kbps`
+        self.output(self.remote_command().arg("--uplimit").arg(kbps.to_string()))
+            .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    pub fn clear_limits(&self) -> Result<(), Error> {
+        // `transmission-remote --no-downlimit --no-uplimit`
+        self.output(
+            self.remote_command()
+                .arg("--no-downlimit")
+                .arg("--no-uplimit"),
+        )
+        .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    // Runs a single action against the selected torrent(s)
+    fn torrent_action(&self, torrent: &str, action: &str) -> Result<(), Error> {
+        self.output(
+            self.remote_command()
+                .arg("--torrent")
+                .arg(torrent)
+                .arg(action),
+        )
+        .and_then(command::check_remote)?;
+        Ok(())
+    }
+
+    // `transmission-remote` with any options that apply to every command
+    fn remote_command(&self) -> Command {
+        let mut command = Command::new(&self.remote_name);
+
+        if let Some(endpoint) = self.opts.rpc_endpoint() {
+            command.arg(endpoint);
+        }
+
+        if let Some(username) = &self.opts.username {
+            let password = self.opts.password.as_deref().unwrap_or_default();
+            command
+                .arg("--auth")
+                .arg(format!("{}:{}", username, password));
+        }
+
+        command
+    }
+
+    fn output(&self, command: &mut Command) -> Result<Output, Error> {
+        command::output(command, self.opts.timeout)
+    }
+
+    fn remove_entry(&mut self, id: u64) -> Option<Entry> {
+        let index = self.entries.iter().position(|entry| entry.id() == &id)?;
+        Some(self.entries.remove(index))
+    }
+
     fn get_mut_by_id(&mut self, id: u64) -> Option<&mut Entry> {
         self.entries.iter_mut().find(|entry| entry.id() == &id)
     }
 
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Entry> {
+        self.entries.iter()
+    }
+
     pub fn get_by_name(&self, name: &str) -> Option<&Entry> {
         self.entries.iter().find(|entry| entry.name() == name)
     }
 
-    pub fn refresh(&mut self) -> Result<(), Error> {
-        let output = Command::new(REMOTE_NAME).arg("--list").output()?;
+    /// Refreshes every `interval` until the torrent with `id` finishes downloading, calling
+    /// `on_progress` with the latest state of the torrent after each refresh
+    pub fn wait_until_complete(
+        &mut self,
+        id: u64,
+        interval: Duration,
+        mut on_progress: impl FnMut(&Entry),
+    ) -> Result<(), Error> {
+        loop {
+            self.refresh()?;
+            let entry = self
+                .entries
+                .iter()
+                .find(|entry| entry.id() == &id)
+                .ok_or(Error::MissingTorrent(id))?;
+
+            on_progress(entry);
+            if entry.is_finished() {
+                return Ok(());
+            }
+
+            thread::sleep(interval);
+        }
+    }
+
+    pub fn summary(&self) -> &TransmissionSummary {
+        &self.summary
+    }
+
+    pub fn refresh(&mut self) -> Result<Vec<EntryChange>, Error> {
+        let output = self
+            .output(self.remote_command().arg("--list"))
+            .and_then(command::check_remote)?;
         let stdout = String::from_utf8(output.stdout)?;
 
         self.update_entries(&stdout)
     }
 
-    fn update_entries(&mut self, s: &str) -> Result<(), Error> {
-        for line in s.lines().skip(1) {
-            if line.trim().starts_with("Sum:") {
-                break;
-            }
+    fn update_entries(&mut self, s: &str) -> Result<Vec<EntryChange>, Error> {
+        let listing = parse_list(s)?;
+        Ok(self.merge_listing(listing))
+    }
 
-            // Parse info for each entry
-            // Each portion is separated by 2 spaces but can have spaces internally
-            let pieces: Vec<_> = line
-                .split("  ")
-                .filter_map(|piece| {
-                    let piece = piece.trim();
-                    if piece.is_empty() {
-                        None
-                    } else {
-                        Some(piece)
-                    }
-                })
-                .collect();
+    fn merge_listing(&mut self, listing: Listing) -> Vec<EntryChange> {
+        let Listing {
+            entries: listed,
+            summary,
+        } = listing;
+        self.summary = summary;
 
-            if pieces.len() != 9 {
-                return Err(Error::InvalidEntryFormat);
-            }
+        let mut changes = Vec::new();
 
-            let id = pieces[0].parse().map_err(|_| Error::InvalidEntryFormat)?;
-            let percentage = if pieces[1] == "n/a" { "0%" } else { pieces[1] };
-            let downloaded = if pieces[2] == "None" {
-                Bytes(0.0)
-            } else {
-                pieces[2].parse()?
-            };
-            let status = pieces[7].parse()?;
-            let name = pieces[8];
+        // Anything that's no longer listed was removed from transmission
+        let (kept, removed) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|entry| listed.iter().any(|listed| listed.id() == entry.id()));
+        self.entries = kept;
+        changes.extend(
+            removed
+                .iter()
+                .map(|entry: &Entry| EntryChange::Removed(*entry.id())),
+        );
+
+        for entry in listed {
+            let id = *entry.id();
 
             // Update the entry if it exists or add a new entry
-            match self.get_mut_by_id(id) {
-                Some(entry) => {
-                    // XXX: the original plan was to use `.update` here, but with the size being
-                    // None getting parsed as 0.0 currently there are issues with the size never
-                    // getting updated to  the correct value.
-                    *entry = Entry::from_id(id)?;
+            match self
+                .entries
+                .iter()
+                .position(|existing| existing.id() == &id)
+            {
+                Some(index) => {
+                    if self.entries[index] != entry {
+                        self.entries[index] = entry;
+                        changes.push(EntryChange::Updated(id));
+                    }
                 }
                 None => {
-                    if percentage == "100%" {
-                        self.entries.push(Entry::completed(
-                            id,
-                            downloaded,
-                            status,
-                            name.to_owned(),
-                        ));
-                    } else {
-                        self.entries.push(Entry::from_id(id)?);
-                    }
+                    self.entries.push(entry);
+                    changes.push(EntryChange::Added(id));
                 }
             }
         }
 
+        changes
+    }
+}
+
+// transmission quietly ignores links it can't use, so catch the obvious mistakes early
+fn validate_magnet(magnet: &str) -> Result<(), Error> {
+    let has_info_hash = magnet.strip_prefix("magnet:?").is_some_and(|params| {
+        params.split('&').any(|param| {
+            param
+                .strip_prefix("xt=urn:btih:")
+                .is_some_and(|info_hash| !info_hash.is_empty())
+        })
+    });
+
+    if has_info_hash {
         Ok(())
+    } else {
+        Err(Error::InvalidMagnet(magnet.to_owned()))
+    }
+}
+
+// Torrents are listed by their name which matches the torrent file's name without the extension
+fn find_listed_id(listing: &str, torrent_file: &Path) -> Result<u64, Error> {
+    let missing = || Error::MissingAddedTorrent(torrent_file.to_owned());
+    let name = torrent_file.file_stem().ok_or_else(missing)?;
+
+    parse_list(listing)?
+        .entries
+        .iter()
+        .find(|listed| name == listed.name().as_str())
+        .map(|listed| *listed.id())
+        .ok_or_else(missing)
+}
+
+fn parse_list(s: &str) -> Result<Listing, Error> {
+    let mut listed = Vec::new();
+    let mut summary = TransmissionSummary::default();
+
+    let mut lines = s.lines();
+    let columns = match lines.next() {
+        Some(header) => Columns::from_header(header)?,
+        None => Columns::default(),
+    };
+
+    for line in lines {
+        let pieces = split_columns(line);
+
+        // The footer is laid out as `Sum: <have> <up> <down>` and ends the listing
+        if pieces.first() == Some(&"Sum:") {
+            summary = parse_summary(&pieces[1..])?;
+            break;
+        }
+
+        // A single odd line shouldn't keep the rest of the entries from being listed
+        match columns.parse_row(&pieces) {
+            Ok(entry) => listed.push(entry),
+            Err(err) => warn!("Skipping unparseable entry {:?} Error: {}", line, err),
+        }
+    }
+
+    Ok(Listing {
+        entries: listed,
+        summary,
+    })
+}
+
+// Where each column lives in the `--list` output. The set of columns varies between transmission
+// versions, so they're located from the header instead of assuming a fixed layout. Only the
+// columns needed to identify an entry are required
+#[derive(Default)]
+struct Columns {
+    count: usize,
+    id: usize,
+    done: usize,
+    have: usize,
+    status: usize,
+    name: usize,
+    eta: Option<usize>,
+    up: Option<usize>,
+    down: Option<usize>,
+    ratio: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &str) -> Result<Self, Error> {
+        let names = split_columns(header);
+        let find = |name| names.iter().position(|column| *column == name);
+        let require = |name| find(name).ok_or(Error::InvalidEntryFormat);
+
+        Ok(Self {
+            count: names.len(),
+            id: require("ID")?,
+            done: require("Done")?,
+            have: require("Have")?,
+            status: require("Status")?,
+            name: require("Name")?,
+            eta: find("ETA"),
+            up: find("Up"),
+            down: find("Down"),
+            ratio: find("Ratio"),
+        })
+    }
+
+    fn parse_row(&self, pieces: &[&str]) -> Result<Entry, Error> {
+        if pieces.len() != self.count {
+            return Err(Error::InvalidEntryFormat);
+        }
+
+        // Torrents with an error get an `*` tacked onto their id
+        let id = pieces[self.id]
+            .trim_end_matches('*')
+            .parse()
+            .map_err(|_| Error::InvalidEntryFormat)?;
+        let downloaded = if pieces[self.have] == "None" {
+            Bytes::zero()
+        } else {
+            pieces[self.have].parse()?
+        };
+        let status = pieces[self.status].parse()?;
+        let name = pieces[self.name];
+        let eta = match self.eta {
+            Some(index) => parse_eta(pieces[index])?,
+            None => None,
+        };
+        let up_rate = match self.up {
+            Some(index) => parse_rate(pieces[index])?,
+            None => Bytes::zero(),
+        };
+        let down_rate = match self.down {
+            Some(index) => parse_rate(pieces[index])?,
+            None => Bytes::zero(),
+        };
+        let ratio = match self.ratio {
+            Some(index) => parse_ratio(pieces[index])?,
+            None => 0.0,
+        };
+
+        let size = parse_size(pieces[self.done], downloaded)?;
+
+        Ok(Entry::new(id, size, downloaded, status, name.to_owned())
+            .with_rates(up_rate, down_rate)
+            .with_ratio(ratio)
+            .with_eta(eta))
+    }
+}
+
+// The listing doesn't include the total size, so it gets worked out from how much has been
+// downloaded so far. The percentage is "n/a" until the torrent's metadata is known, which leaves
+// the size as unknown
+fn parse_size(done: &str, downloaded: Bytes) -> Result<Option<Bytes>, Error> {
+    if done == "n/a" {
+        return Ok(None);
+    }
+
+    let percentage: f64 = done
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| Error::InvalidEntryFormat)?;
+    if percentage >= 100.0 {
+        Ok(Some(downloaded))
+    } else if percentage > 0.0 {
+        Ok(Some(Bytes(
+            (downloaded.0 as f64 * 100.0 / percentage).round() as u64,
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+// Each portion is separated by 2 spaces but can have spaces internally
+fn split_columns(line: &str) -> Vec<&str> {
+    line.split("  ")
+        .filter_map(|piece| {
+            let piece = piece.trim();
+            if piece.is_empty() {
+                None
+            } else {
+                Some(piece)
+            }
+        })
+        .collect()
+}
+
+fn parse_summary(pieces: &[&str]) -> Result<TransmissionSummary, Error> {
+    match pieces {
+        [downloaded, up_rate, down_rate] => Ok(TransmissionSummary {
+            downloaded: if *downloaded == "None" {
+                Bytes::zero()
+            } else {
+                downloaded.parse()?
+            },
+            up_rate: parse_rate(up_rate)?,
+            down_rate: parse_rate(down_rate)?,
+        }),
+        _ => Err(Error::InvalidEntryFormat),
+    }
+}
+
+impl Drop for Transmission {
+    fn drop(&mut self) {
+        if self.holds_lock {
+            HANDLE_LOCK.store(false, Ordering::SeqCst);
+        }
     }
 }
 
@@ -201,7 +754,7 @@ mod tests {
 
     use std::{fs, path::Path};
 
-    use crate::{bytes::Bytes, entry::Status};
+    use crate::{bytes::Bytes, entry::Status, test_utils::FakeRemote};
 
     type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
 
@@ -214,17 +767,602 @@ mod tests {
         transmission.update_entries(&entry_list)?;
 
         let name = "archlinux-2021.04.01-x86_64.iso";
-        let entry = Entry::completed(
-            1,
-            Bytes::from(786.8 * 1_000_000.0),
-            Status::Seeding,
-            name.to_owned(),
-        );
-        assert_eq!(transmission.entries, [entry.clone()]);
+        let entry = Entry::completed(1, Bytes(786_800_000), Status::Seeding, name.to_owned())
+            .with_rates(Bytes(7_000), Bytes::zero())
+            .with_ratio(0.2);
+        assert_eq!(transmission.entries, std::slice::from_ref(&entry));
         assert_eq!(transmission.get_by_name(name), Some(&entry));
+        assert_eq!(entry.up_rate(), &Bytes(7_000));
+        assert_eq!(entry.down_rate(), &Bytes::zero());
+        assert_eq!(entry.ratio(), &0.2);
+        assert_eq!(entry.eta(), &None);
 
         transmission.stop();
 
         Ok(())
     }
+
+    #[test]
+    fn entry_changes() -> BoxResult<()> {
+        let corpus = Path::new("tests").join("corpus");
+        let downloading_list = fs::read_to_string(corpus.join("entry_list_8_columns.txt"))?;
+        let finished_list = fs::read_to_string(corpus.join("entry_list_multiple.txt"))?;
+        let single_list = fs::read_to_string(corpus.join("entry_list.txt"))?;
+
+        // Everything comes from the listing, so the remote should never get called
+        let remote = FakeRemote::new("exit 1")?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+
+        let changes = transmission.update_entries(&downloading_list)?;
+        assert_eq!(changes, [EntryChange::Added(1), EntryChange::Added(2)]);
+        let downloading = transmission.get_by_name("ubuntu-20.04.2.0-desktop-amd64.iso");
+        assert_eq!(downloading.map(Entry::status), Some(&Status::Downloading));
+        assert_eq!(
+            downloading.map(Entry::size),
+            Some(&Some(Bytes(2_888_888_889)))
+        );
+
+        let changes = transmission.update_entries(&finished_list)?;
+        assert_eq!(changes, [EntryChange::Updated(1), EntryChange::Updated(2)]);
+        let finished = transmission.get_by_name("ubuntu-20.04.2.0-desktop-amd64.iso");
+        assert_eq!(finished.map(Entry::size), Some(&Some(Bytes(2_890_000_000))));
+        assert_eq!(finished.map(Entry::is_finished), Some(true));
+
+        let changes = transmission.update_entries(&single_list)?;
+        assert_eq!(changes, [EntryChange::Removed(2)]);
+        assert_eq!(transmission.entries.len(), 1);
+
+        // Nothing changes when the info stays the same
+        let changes = transmission.update_entries(&single_list)?;
+        assert!(changes.is_empty());
+
+        assert!(remote.calls().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn size_from_later_listing() -> BoxResult<()> {
+        let entry_list =
+            fs::read_to_string(Path::new("tests").join("corpus").join("entry_list.txt"))?;
+        let magnet_list = entry_list
+            .replace("100%   786.8 MB", " n/a       None")
+            .replace("Seeding     ", "Downloading ");
+
+        let mut transmission = Transmission::empty(TransmissionOpts::new());
+        transmission.update_entries(&magnet_list)?;
+        assert_eq!(transmission.entries[0].size(), &None);
+        assert!(!transmission.entries[0].is_finished());
+
+        // Once the metadata shows up the real size replaces the unknown one
+        let changes = transmission.update_entries(&entry_list)?;
+        assert_eq!(changes, [EntryChange::Updated(1)]);
+        assert_eq!(transmission.entries[0].size(), &Some(Bytes(786_800_000)));
+        assert!(transmission.entries[0].is_finished());
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_entries() -> BoxResult<()> {
+        let entry_list = fs::read_to_string(
+            Path::new("tests")
+                .join("corpus")
+                .join("entry_list_multiple.txt"),
+        )?;
+
+        let mut transmission = Transmission::empty(TransmissionOpts::new());
+        assert!(transmission.entries().is_empty());
+
+        transmission.update_entries(&entry_list)?;
+        assert_eq!(transmission.entries().len(), 2);
+        let names: Vec<_> = transmission
+            .iter()
+            .map(|entry| entry.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "archlinux-2021.04.01-x86_64.iso",
+                "ubuntu-20.04.2.0-desktop-amd64.iso"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_summary_line() -> BoxResult<()> {
+        let entry_list = fs::read_to_string(
+            Path::new("tests")
+                .join("corpus")
+                .join("entry_list_multiple.txt"),
+        )?;
+
+        let mut transmission = Transmission::empty(TransmissionOpts::new());
+        assert_eq!(transmission.summary(), &TransmissionSummary::default());
+
+        transmission.update_entries(&entry_list)?;
+        let summary = transmission.summary();
+        assert_eq!(summary.downloaded(), &Bytes(3_670_000_000));
+        assert_eq!(summary.up_rate(), &Bytes(7_000));
+        assert_eq!(summary.down_rate(), &Bytes::zero());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_column_variants() -> BoxResult<()> {
+        let corpus = Path::new("tests").join("corpus");
+
+        // No ratio column
+        let listing = parse_list(&fs::read_to_string(
+            corpus.join("entry_list_8_columns.txt"),
+        )?)?;
+        let ids: Vec<_> = listing.entries.iter().map(|listed| *listed.id()).collect();
+        assert_eq!(ids, [1, 2]);
+        let name = "archlinux-2021.04.01-x86_64.iso";
+        assert_eq!(
+            listing.entries[0],
+            Entry::completed(1, Bytes(786_800_000), Status::Seeding, name.to_owned())
+                .with_rates(Bytes(7_000), Bytes::zero())
+        );
+        assert_eq!(listing.entries[1].status(), &Status::Downloading);
+        assert_eq!(
+            listing.entries[1].eta(),
+            &Some(Duration::from_secs(10 * 60))
+        );
+
+        // An extra peers column along with a broken row that gets skipped
+        let listing = parse_list(&fs::read_to_string(
+            corpus.join("entry_list_10_columns.txt"),
+        )?)?;
+        assert_eq!(listing.entries.len(), 1);
+        assert_eq!(
+            listing.entries[0],
+            Entry::completed(1, Bytes(786_800_000), Status::Seeding, name.to_owned())
+                .with_rates(Bytes(7_000), Bytes::zero())
+                .with_ratio(0.2)
+        );
+        assert_eq!(listing.summary.downloaded(), &Bytes(786_800_000));
+
+        // Without the columns needed to identify entries there's nothing to go off of
+        assert!(matches!(
+            parse_list("    ID   Done  Status"),
+            Err(Error::InvalidEntryFormat)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait_for_completion() -> BoxResult<()> {
+        let corpus = Path::new("tests").join("corpus");
+        let entry_list = fs::read_to_string(corpus.join("entry_list.txt"))?;
+
+        // Each `--list` call reports more of the torrent as downloaded
+        let remote = FakeRemote::new(
+            r#"dir=$(dirname "$0")
+cat "$dir/list_$(grep -c -- --list "$dir/args.log")""#,
+        )?;
+        let dir = remote.dir.path();
+        let progress_steps = [" 33%   262.3 MB", " 67%   524.5 MB", "100%   786.8 MB"];
+        for (call, done_and_have) in progress_steps.iter().enumerate() {
+            fs::write(
+                dir.join(format!("list_{}", call + 1)),
+                entry_list.replace("100%   786.8 MB", done_and_have),
+            )?;
+        }
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+
+        let mut progress = Vec::new();
+        transmission.wait_until_complete(1, Duration::from_millis(1), |entry| {
+            progress.push(*entry.downloaded())
+        })?;
+        assert_eq!(
+            progress,
+            [Bytes(262_300_000), Bytes(524_500_000), Bytes(786_800_000)]
+        );
+
+        // The torrent getting removed means it'll never finish
+        fs::write(
+            dir.join("list_4"),
+            "    ID   Done       Have  ETA           Up    Down  Ratio  Status       Name\n",
+        )?;
+        assert!(matches!(
+            transmission.wait_until_complete(1, Duration::from_millis(1), |_| {}),
+            Err(Error::MissingTorrent(1))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn running_daemon() -> BoxResult<()> {
+        // Nothing should be listening on this port
+        let opts = TransmissionOpts::new().host("127.0.0.1".to_owned()).port(1);
+        assert!(Transmission::from_running(opts)?.is_none());
+
+        let remote = FakeRemote::new("exit 0")?;
+        assert!(remote
+            .transmission(TransmissionOpts::new())
+            .answering()
+            .is_some());
+        assert_eq!(remote.calls(), ["--session-info"]);
+
+        let remote = FakeRemote::new("exit 1")?;
+        assert!(remote
+            .transmission(TransmissionOpts::new())
+            .answering()
+            .is_none());
+
+        // Even when transmission-remote exits cleanly after failing to reach the daemon
+        let remote = FakeRemote::new(
+            "echo \"transmission-remote: (http://localhost:9091/transmission/rpc/) Couldn't \
+                connect to server\" >&2",
+        )?;
+        assert!(remote
+            .transmission(TransmissionOpts::new())
+            .answering()
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn added_torrent_id() -> BoxResult<()> {
+        let corpus = Path::new("tests").join("corpus");
+        // The id gets picked out of the listing after adding
+        let remote = FakeRemote::new(&format!(
+            r#"case "$*" in
+    *--add*) cat '{}' ;;
+    *--list*) cat '{}' ;;
+esac"#,
+            fs::canonicalize(corpus.join("add_response.txt"))?.display(),
+            fs::canonicalize(corpus.join("entry_list.txt"))?.display()
+        ))?;
+        let transmission = remote.transmission(TransmissionOpts::new());
+        let torrent_file = Path::new("archlinux-2021.04.01-x86_64.iso.torrent");
+        assert_eq!(transmission.seed_local_torrent(torrent_file)?, 1);
+        assert!(matches!(
+            transmission.seed_local_torrent(Path::new("other.torrent")),
+            Err(Error::MissingAddedTorrent(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_failure() -> BoxResult<()> {
+        let remote = FakeRemote::new(
+            "echo \"transmission-remote: (http://localhost:9091/transmission/rpc/) Couldn't \
+                connect to server\" >&2",
+        )?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+
+        assert!(matches!(
+            transmission.refresh(),
+            Err(Error::RemoteCommandFailed(_))
+        ));
+        assert!(matches!(
+            transmission
+                .download_torrent("magnet:?xt=urn:btih:b55c37be98627d3acb1dd713a33fe1882b01ca02"),
+            Err(Error::RemoteCommandFailed(_))
+        ));
+        assert!(matches!(
+            transmission.seed_local_torrent(Path::new("sample.torrent")),
+            Err(Error::RemoteCommandFailed(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_auth() {
+        let transmission = Transmission::empty(TransmissionOpts::new());
+        let command = transmission.remote_command();
+        assert_eq!(command.get_args().count(), 0);
+
+        let transmission = Transmission::empty(
+            TransmissionOpts::new()
+                .username("user".to_owned())
+                .password("pass".to_owned()),
+        );
+        let command = transmission.remote_command();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["--auth", "user:pass"]);
+    }
+
+    #[test]
+    fn download_file_command() {
+        let torrent_file = Path::new("package.torrent");
+
+        let transmission = Transmission::empty(TransmissionOpts::new());
+        let command = transmission.download_file_command(torrent_file);
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["--add", "package.torrent", "--start"]);
+
+        let transmission =
+            Transmission::empty(TransmissionOpts::new().download_dir(PathBuf::from("data")));
+        let command = transmission.download_file_command(torrent_file);
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--add",
+                "package.torrent",
+                "--start",
+                "--download-dir",
+                "data"
+            ]
+        );
+    }
+
+    #[test]
+    fn labeled_commands() {
+        let transmission = Transmission::empty(
+            TransmissionOpts::new().labels(vec!["distpac".to_owned(), "extra".to_owned()]),
+        );
+
+        let command = transmission.seed_command(Path::new("package.torrent"));
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--torrent",
+                "package.torrent",
+                "--add",
+                "package.torrent",
+                "--verify",
+                "--start",
+                "--labels",
+                "distpac,extra"
+            ]
+        );
+
+        let command = transmission.download_command("magnet:?xt=urn:btih:abc");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--add",
+                "magnet:?xt=urn:btih:abc",
+                "--labels",
+                "distpac,extra"
+            ]
+        );
+    }
+
+    #[test]
+    fn remote_endpoint() {
+        let transmission = Transmission::empty(
+            TransmissionOpts::new()
+                .host("seeder.local".to_owned())
+                .port(9092),
+        );
+        let command = transmission.remote_command();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["seeder.local:9092"]);
+
+        // Missing pieces fall back to the defaults
+        let transmission = Transmission::empty(TransmissionOpts::new().port(9092));
+        let command = transmission.remote_command();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["localhost:9092"]);
+
+        let transmission = Transmission::empty(
+            TransmissionOpts::new()
+                .host("seeder.local".to_owned())
+                .username("user".to_owned())
+                .password("pass".to_owned()),
+        );
+        let command = transmission.remote_command();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["seeder.local:9091", "--auth", "user:pass"]);
+    }
+
+    #[test]
+    fn missing_remote() {
+        let mut transmission = Transmission::empty(TransmissionOpts::new());
+        transmission.remote_name = "distpac-missing-remote".to_owned();
+
+        match transmission.refresh() {
+            Err(Error::BinaryNotFound(name)) => assert_eq!(name, "distpac-missing-remote"),
+            other => panic!("Expected a missing binary error, got {:?}", other),
+        }
+        assert!(matches!(
+            transmission.download_torrent("magnet:?xt=urn:btih:abc"),
+            Err(Error::BinaryNotFound(_))
+        ));
+        assert!(matches!(
+            transmission.pause_all(),
+            Err(Error::BinaryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn magnet_validation() {
+        let magnet = "magnet:?xt=urn:btih:b55c37be98627d3acb1dd713a33fe1882b01ca02\
+            &dn=archlinux-2021.04.01-x86%5F64.iso";
+        assert!(validate_magnet(magnet).is_ok());
+        assert!(validate_magnet("magnet:?dn=name&xt=urn:btih:b55c37be").is_ok());
+
+        for bad in [
+            "",
+            "garbage",
+            "magnet:?dn=name",
+            "magnet:?xt=urn:btih:",
+            "xt=urn:btih:b5",
+        ]
+        .iter()
+        {
+            assert!(matches!(validate_magnet(bad), Err(Error::InvalidMagnet(_))));
+        }
+
+        // Invalid links never make it to transmission
+        let transmission = Transmission::empty(TransmissionOpts::new());
+        assert!(matches!(
+            transmission.download_torrent("garbage"),
+            Err(Error::InvalidMagnet(_))
+        ));
+    }
+
+    #[test]
+    fn bandwidth_limits() -> BoxResult<()> {
+        let remote = FakeRemote::new("")?;
+        let transmission = remote.transmission(TransmissionOpts::new());
+
+        transmission.set_download_limit(500)?;
+        transmission.set_upload_limit(100)?;
+        transmission.clear_limits()?;
+        assert_eq!(
+            remote.calls(),
+            [
+                "--downlimit 500",
+                "--uplimit 100",
+                "--no-downlimit --no-uplimit"
+            ]
+        );
+
+        // Transmission rejecting the limits gets surfaced too
+        let remote = FakeRemote::new(
+            "echo \"transmission-remote: (http://localhost:9091/transmission/rpc/) Unable to \
+                set the limit\" >&2",
+        )?;
+        let transmission = remote.transmission(TransmissionOpts::new());
+        for result in [
+            transmission.set_download_limit(500),
+            transmission.set_upload_limit(100),
+            transmission.clear_limits(),
+        ]
+        .iter()
+        {
+            assert!(matches!(result, Err(Error::RemoteCommandFailed(_))));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn single_handle() {
+        let first = Transmission::locked(TransmissionOpts::new()).unwrap();
+        assert!(matches!(
+            Transmission::locked(TransmissionOpts::new()),
+            Err(Error::AlreadyRunning)
+        ));
+
+        // And the lock is released once the handle is gone
+        drop(first);
+        assert!(Transmission::locked(TransmissionOpts::new()).is_ok());
+    }
+
+    #[test]
+    fn remove_entries() -> BoxResult<()> {
+        let sample_file = Path::new("tests").join("corpus").join("entry_list.txt");
+        let entry_list = fs::read_to_string(sample_file)?;
+
+        let mut transmission = Transmission::empty(TransmissionOpts::new());
+        transmission.update_entries(&entry_list)?;
+        assert_eq!(transmission.entries.len(), 1);
+
+        // Removing a missing torrent errors out before ever calling transmission
+        assert!(matches!(
+            transmission.remove_torrent(2),
+            Err(Error::MissingTorrent(2))
+        ));
+        assert!(matches!(
+            transmission.remove_torrent_with_data(2),
+            Err(Error::MissingTorrent(2))
+        ));
+        assert_eq!(transmission.entries.len(), 1);
+
+        let removed = transmission.remove_entry(1);
+        assert_eq!(removed.map(|entry| *entry.id()), Some(1));
+        assert!(transmission.entries.is_empty());
+        assert_eq!(transmission.remove_entry(1), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_from_remote() -> BoxResult<()> {
+        let sample_file = Path::new("tests").join("corpus").join("entry_list.txt");
+        let entry_list = fs::read_to_string(sample_file)?;
+
+        let remote = FakeRemote::new("exit 0")?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+        transmission.update_entries(&entry_list)?;
+        transmission.remove_torrent(1)?;
+        assert!(transmission.entries.is_empty());
+        assert_eq!(remote.calls(), ["--torrent 1 --remove"]);
+
+        // The entry sticks around when transmission didn't actually remove the torrent
+        let remote = FakeRemote::new("exit 1")?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+        transmission.update_entries(&entry_list)?;
+        assert!(matches!(
+            transmission.remove_torrent(1),
+            Err(Error::RemoteCommandFailed(_))
+        ));
+        assert_eq!(transmission.entries.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_purge() -> BoxResult<()> {
+        let sample_file = Path::new("tests").join("corpus").join("entry_list.txt");
+        let entry_list = fs::read_to_string(sample_file)?;
+
+        // A failed purge leaves the data on disk, so it has to be reported
+        let remote = FakeRemote::new(
+            "echo \"transmission-remote: (http://localhost:9091/transmission/rpc/) Couldn't \
+                connect to server\" >&2",
+        )?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+        transmission.update_entries(&entry_list)?;
+        assert!(matches!(
+            transmission.remove_torrent_with_data(1),
+            Err(Error::RemoteCommandFailed(_))
+        ));
+        assert_eq!(transmission.entries.len(), 1);
+        assert_eq!(remote.calls(), ["--torrent 1 --remove-and-delete"]);
+
+        let remote = FakeRemote::new("exit 0")?;
+        let mut transmission = remote.transmission(TransmissionOpts::new());
+        transmission.update_entries(&entry_list)?;
+        transmission.remove_torrent_with_data(1)?;
+        assert!(transmission.entries.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_pause_and_resume() -> BoxResult<()> {
+        let remote = FakeRemote::new("exit 1")?;
+        let transmission = remote.transmission(TransmissionOpts::new());
+
+        // Callers like the client's seeding policy rely on these failing to warn about it
+        for result in [
+            transmission.pause_torrent(1),
+            transmission.resume_torrent(1),
+            transmission.pause_all(),
+            transmission.resume_all(),
+        ]
+        .iter()
+        {
+            assert!(matches!(result, Err(Error::RemoteCommandFailed(_))));
+        }
+        assert_eq!(
+            remote.calls(),
+            [
+                "--torrent 1 --stop",
+                "--torrent 1 --start",
+                "--torrent all --stop",
+                "--torrent all --start"
+            ]
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,46 @@
+use tempfile::TempDir;
+
+use std::{fs, io, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use crate::{Transmission, TransmissionOpts};
+
+// A stand-in for `transmission-remote` that runs the provided shell script. The arguments for each
+// call get logged to `args.log` in the temp dir
+pub(crate) struct FakeRemote {
+    pub dir: TempDir,
+    pub path: PathBuf,
+}
+
+impl FakeRemote {
+    pub fn new(script: &str) -> io::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("fake-remote");
+        let args_log = dir.path().join("args.log");
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> '{}'\n{}\n",
+                args_log.display(),
+                script
+            ),
+        )?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+
+        Ok(Self { dir, path })
+    }
+
+    pub fn transmission(&self, opts: TransmissionOpts) -> Transmission {
+        let mut transmission = Transmission::empty(opts);
+        transmission.remote_name = self.path.to_string_lossy().into_owned();
+        transmission
+    }
+
+    // Each call's arguments in the order they were called
+    pub fn calls(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.path().join("args.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+}